@@ -3,26 +3,399 @@
  */
 
 (function () {    
+    // Helpers that need nothing from NetsBlox but its List class, kept in their own file so they can be tested on their own
+
+    // Add saved state to the end of the stage's XML. Sliced rather than replaced, since replacement strings expand `$&` and the like
+    function ExampleExtension_insertState(xml, stateXml) {
+        const end = '</stage>';
+        if (!xml.endsWith(end)) {
+            return xml;
+        }
+        return xml.slice(0, -end.length) + stateXml + end;
+    }
+
+    // Byte and number vectors are passed as typed arrays, which wasm-bindgen copies in one go instead of item by item
+    function ExampleExtension_arrayItems(value, expected) {
+        if (value === null || value === undefined || value === '') {
+            return [];
+        }
+        const items = value instanceof List ? value.itemsArray() : value;
+        if (!Array.isArray(items) && !ArrayBuffer.isView(items)) {
+            throw new Error(`expected ${expected}`);
+        }
+        return items;
+    }
+
+    function ExampleExtension_toBytes(value) {
+        if (value instanceof Uint8Array) {
+            return value;
+        }
+        if (typeof value === 'string' && value !== '') {
+            // Text is base64, optionally as a data URL
+            try {
+                const binary = atob(value.replace(/^data:[^,]*;base64,/, '').trim());
+                return Uint8Array.from(binary, c => c.charCodeAt(0));
+            } catch {
+                throw new Error('expected a list of bytes or base64 text');
+            }
+        }
+        const items = ExampleExtension_arrayItems(value, 'a list of bytes or base64 text');
+        const bytes = new Uint8Array(items.length);
+        for (let i = 0; i < items.length; i++) {
+            const x = Number(items[i]);
+            // Number() reads blank text as 0, so blank items are rejected explicitly
+            if (!Number.isInteger(x) || x < 0 || x > 255 || typeof items[i] === 'boolean' || String(items[i]).trim() === '') {
+                throw new Error(`item ${i + 1}: expected a byte from 0 to 255, got ${items[i]}`);
+            }
+            bytes[i] = x;
+        }
+        return bytes;
+    }
+
+    function ExampleExtension_toNumbers(value) {
+        if (value instanceof Float64Array) {
+            return value;
+        }
+        const items = ExampleExtension_arrayItems(value, 'a list of numbers');
+        if (ArrayBuffer.isView(items)) {
+            return Float64Array.from(items);
+        }
+        const numbers = new Float64Array(items.length);
+        for (let i = 0; i < items.length; i++) {
+            const x = Number(items[i]);
+            if (Number.isNaN(x) || typeof items[i] === 'boolean' || String(items[i]).trim() === '') {
+                throw new Error(`item ${i + 1}: expected a number, got ${items[i]}`);
+            }
+            numbers[i] = x;
+        }
+        return numbers;
+    }
+
+    // Translations from the locales directory, keyed by NetsBlox language code
+    const ExampleExtension_locales = {
+		"es": {
+			blocks: {"defaultAdder":["sumar %n + %n",[1,0]],"logHelloName":["Registrar hola %s",null],"logHelloWorld":["Registrar ¡Hola Mundo!",null],"repeatString":["Repetir %times veces %s",[1,0]]},
+			categories: {"Hello World":"Hola Mundo"},
+			settings: {"exampleextensionallcaps.name":"Salida en mayúsculas del elemento de menú","exampleextensionallcaps.off_hint":"No poner la salida en mayúsculas","exampleextensionallcaps.on_hint":"Poner la salida en mayúsculas"},
+			menu: {"Print Extension Name":"Imprimir nombre de la extensión","Print Hello World":"Imprimir Hola Mundo"},
+		},
+
+    };
+    function ExampleExtension_translate(kind, key, fallback) {
+        const locale = ExampleExtension_locales[SnapTranslator.language];
+        const res = locale && locale[kind][key];
+        return res === undefined ? fallback : res;
+    }
+    // Translated specs may order slots differently, so inputs are mapped back to the original order before calling the block
+    function ExampleExtension_localizeBlock(name, spec, defaults, fn) {
+        const [trSpec, order] = ExampleExtension_translate('blocks', name, [spec, null]);
+        if (!order) {
+            return [trSpec, defaults, fn];
+        }
+        return [trSpec, order.map(i => defaults[i]), function (...args) {
+            const inputs = [];
+            order.forEach((j, i) => inputs[j] = args[i]);
+            return fn.apply(this, inputs);
+        }];
+    }
+    if (Object.keys(ExampleExtension_locales).length > 0) {
+        const translate = SnapTranslator.translate;
+        SnapTranslator.translate = function (string) {
+            return ExampleExtension_translate('categories', string, undefined) ?? translate.apply(this, arguments);
+        };
+    }
+
+    // Cancellation tokens are created for every async block call and cancelled when it times out or its process is stopped
+    function ExampleExtension_token() {
+        let resolve;
+        const token = { cancelled: false, reason: null, promise: new Promise(r => resolve = r) };
+        token.cancel = reason => {
+            if (!token.cancelled) {
+                Object.assign(token, { cancelled: true, reason });
+                resolve(reason);
+            }
+        };
+        return token;
+    }
+
+    function ExampleExtension_cancel(proc, reason) {
+        for (const token of proc.rustTokens ?? []) {
+            token.cancel(reason);
+        }
+        proc.rustTokens?.clear();
+        // Released once more so blocks checking their token can stop. No block is awaited anymore, so later yields
+        // complete on the next task, letting blocks that ignore their token run to the end and be dropped
+        proc.rustAwaiting = null;
+        const yields = proc.rustYields ?? [];
+        proc.rustYields = [];
+        yields.forEach(resolve => resolve());
+        for (const call of proc.rustRings ?? []) {
+            call.process.stop();
+            call.reject(new Error('ring was cancelled'));
+        }
+        proc.rustRings = [];
+    }
+
+    const ExampleExtension_stop = Process.prototype.stop;
+    Process.prototype.stop = function () {
+        ExampleExtension_cancel(this, 'stopped');
+        return ExampleExtension_stop.apply(this, arguments);
+    };
+
+    // Async blocks are re-evaluated every frame until their promise settles, stepping any rings they called meanwhile
+    function ExampleExtension_await(proc, start, timeoutMs) {
+        const context = proc.context;
+        if (!context.rustAsync) {
+            const token = ExampleExtension_token();
+            const state = context.rustAsync = { done: false, timedOut: false, token };
+            (proc.rustTokens ??= new Set()).add(token);
+            proc.rustAwaiting = state;
+            // A timer rather than a check each frame, so the block is cancelled on time even if frames are slow or paused
+            const timer = timeoutMs === undefined ? null : setTimeout(() => {
+                state.timedOut = true;
+                ExampleExtension_cancel(proc, 'timeout');
+            }, timeoutMs);
+            start(token).then(value => Object.assign(state, { done: true, value }), error => Object.assign(state, { done: true, error }))
+                .finally(() => {
+                    clearTimeout(timer);
+                    proc.rustTokens.delete(token);
+                    if (proc.rustAwaiting === state) {
+                        proc.rustAwaiting = null;
+                    }
+                });
+        }
+
+        if (context.rustAsync.timedOut) {
+            context.rustAsync = null;
+            throw new Error(`timed out after ${timeoutMs} ms`);
+        }
+
+        // Blocks waiting for the next step continue now
+        const yields = proc.rustYields ?? [];
+        proc.rustYields = [];
+        yields.forEach(resolve => resolve());
+
+        for (const call of [...(proc.rustRings ?? [])]) {
+            call.process.runStep();
+            if (call.process.errorFlag || !call.process.isRunning()) {
+                proc.rustRings.splice(proc.rustRings.indexOf(call), 1);
+                if (call.process.errorFlag) {
+                    call.reject(new Error('error while running ring'));
+                } else {
+                    call.resolve(call.process.homeContext.inputs[0]);
+                }
+            }
+        }
+
+        const state = context.rustAsync;
+        if (state.done) {
+            context.rustAsync = null;
+            if ('error' in state) {
+                throw state.error;
+            }
+            return state.value;
+        }
+
+        proc.pushContext('doYield');
+        proc.pushContext();
+    }
+
+    // C-slots pass their enclosed script, which is wrapped as a ring that can see the calling script's variables
+    function ExampleExtension_script(proc, script) {
+        if (!script) {
+            return null;
+        }
+        return new Context(null, script.blockSequence(), proc.context.outerContext, proc.receiver);
+    }
+
+    function ExampleExtension_fromArray(value) {
+        return ArrayBuffer.isView(value) ? new List(Array.from(value)) : value;
+    }
+
+    // Blocks declared with worker = true run on Web Workers, each with its own instance of the module.
+    // Every running call has a worker to itself, so a long call doesn't hold up the others, and idle workers are reused.
+    const ExampleExtension_workers = { available: typeof Worker !== 'undefined', url: null, idle: [] };
+
+    function ExampleExtension_toPlain(value) {
+        return value instanceof List ? value.itemsArray().map(ExampleExtension_toPlain) : value;
+    }
+
+    function ExampleExtension_runLocally(fn, args) {
+        return Promise.resolve().then(() => window.ExampleExtension_fns[fn](...args));
+    }
+
+    function ExampleExtension_spawnWorker() {
+        const workers = ExampleExtension_workers;
+        workers.url ??= URL.createObjectURL(new Blob([`
+            const ready = import('${path}/pkg/netsblox_extension_rs.js').then(async m => { await m.default(); return m; });
+            // Reported apart from errors in calls, so the main thread can run blocks itself instead
+            ready.catch(error => postMessage({ loadError: String(error?.message ?? error) }));
+            self.onmessage = async e => {
+                const { fn, args } = e.data;
+                const m = await ready.catch(() => null);
+                if (!m) {
+                    return;
+                }
+                try {
+                    postMessage({ value: await m[fn](...args) });
+                } catch (error) {
+                    postMessage({ error: String(error?.message ?? error) });
+                }
+            };`], { type: 'text/javascript' }));
+        return new Worker(workers.url, { type: 'module' });
+    }
+
+    function ExampleExtension_worker(fn, args, token) {
+        const workers = ExampleExtension_workers;
+        // Lists are sent as arrays on both paths, so blocks see the same values either way
+        args = args.map(ExampleExtension_toPlain);
+        let worker = null;
+        if (workers.available) {
+            try {
+                worker = workers.idle.pop() ?? ExampleExtension_spawnWorker();
+            } catch {
+                workers.available = false;
+            }
+        }
+        if (!worker) {
+            return ExampleExtension_runLocally(fn, args);
+        }
+
+        return new Promise((resolve, reject) => {
+            let settled = false;
+            const settle = f => x => {
+                settled = true;
+                f(x);
+            };
+            resolve = settle(resolve);
+            reject = settle(reject);
+            // A computation on a worker can't be interrupted, so the worker is dropped and a new one made for later calls
+            token.promise.then(reason => {
+                if (!settled) {
+                    worker.terminate();
+                    reject(new Error(`block was cancelled (${reason})`));
+                }
+            });
+
+            const release = () => {
+                worker.onmessage = worker.onerror = null;
+                if (workers.idle.length < (navigator.hardwareConcurrency ?? 4)) {
+                    workers.idle.push(worker);
+                } else {
+                    worker.terminate();
+                }
+            };
+            const fallBack = () => {
+                // The worker could not load the module, so this and later calls run on the main thread
+                workers.available = false;
+                worker.terminate();
+                workers.idle.forEach(x => x.terminate());
+                workers.idle = [];
+                ExampleExtension_runLocally(fn, args).then(resolve, reject);
+            };
+            worker.onmessage = e => {
+                if ('loadError' in e.data) {
+                    fallBack();
+                } else if ('error' in e.data) {
+                    // The module may be left in a bad state, e.g. after a panic, so the worker is not reused
+                    worker.terminate();
+                    reject(new Error(e.data.error));
+                } else {
+                    release();
+                    resolve(e.data.value);
+                }
+            };
+            worker.onerror = e => {
+                e.preventDefault();
+                fallBack();
+            };
+            try {
+                worker.postMessage({ fn, args });
+            } catch {
+                release();
+                reject(new Error('blocks run on a worker only take text, numbers, booleans and lists'));
+            }
+        });
+    }
+
+    // State registered with persist is saved in the stage's XML and handed back when the role is opened
+    let ExampleExtension_loadedState = null;
+    let ExampleExtension_stateLoadPending = false;
+
+    function ExampleExtension_stateError(action, error) {
+        console.error(error);
+        world.children[0]?.showMessage?.(`Example Extension could not ${action} its state:\n${error?.message ?? error}`);
+    }
+
+    // Roles can be opened before the module is initialized, in which case the state is loaded once it is
+    function ExampleExtension_loadState() {
+        const fns = window.ExampleExtension_fns;
+        if (!fns?.netsblox_extension_load_state) {
+            ExampleExtension_stateLoadPending = true;
+            return;
+        }
+        const state = ExampleExtension_loadedState;
+        ExampleExtension_loadedState = null;
+        ExampleExtension_stateLoadPending = false;
+        try {
+            fns.netsblox_extension_load_state(state);
+        } catch (error) {
+            ExampleExtension_stateError('load', error);
+        }
+    }
+    window.ExampleExtension_initialized = () => {
+        if (ExampleExtension_stateLoadPending) {
+            ExampleExtension_loadState();
+        }
+    };
+
+    const ExampleExtension_toXML = StageMorph.prototype.toXML;
+    StageMorph.prototype.toXML = function (serializer) {
+        const xml = ExampleExtension_toXML.apply(this, arguments);
+        let state = null;
+        try {
+            state = window.ExampleExtension_fns?.netsblox_extension_save_state();
+        } catch (error) {
+            ExampleExtension_stateError('save', error); // The project is still saved, just without the extension's state
+        }
+        if (state === null || state === undefined) {
+            return xml;
+        }
+        return ExampleExtension_insertState(xml, serializer.format('<rust-state extension="@">$</rust-state>', 'ExampleExtension', state));
+    };
+
+    const ExampleExtension_loadScene = SnapSerializer.prototype.loadScene;
+    SnapSerializer.prototype.loadScene = function (xmlNode) {
+        const stage = xmlNode.childNamed('stage');
+        const state = stage?.children.find(x => x.tag === 'rust-state' && x.attributes.extension === 'ExampleExtension');
+        ExampleExtension_loadedState = state ? state.contents : null;
+        return ExampleExtension_loadScene.apply(this, arguments);
+    };
+
     class ExampleExtension extends Extension {
         constructor(ide) {
             super('Example Extension');
+
         }
 
         onOpenRole() {
+            window.ExampleExtension_fns?.netsblox_extension_open_role();
+            ExampleExtension_loadState();
 
         }
 
         getSettings() {
             return [
-				Extension.ExtensionSetting.createFromLocalStorage('All Caps output from Menu Item', 'exampleextensionallcaps', false, 'Capitalize output', 'Do not capitalize output', false),
+				Extension.ExtensionSetting.createFromLocalStorage(ExampleExtension_translate('settings', 'exampleextensionallcaps.name', 'All Caps output from Menu Item'), 'exampleextensionallcaps', false, ExampleExtension_translate('settings', 'exampleextensionallcaps.on_hint', 'Capitalize output'), ExampleExtension_translate('settings', 'exampleextensionallcaps.off_hint', 'Do not capitalize output'), false),
 
             ];
         }
 
         getMenu() {
             return {
-				'Print Hello World': window.ExampleExtension_fns.print_hello_world,
-				'Print Extension Name': window.ExampleExtension_fns.print_extension_name,
+				[ExampleExtension_translate('menu', 'Print Hello World', 'Print Hello World')]: window.ExampleExtension_fns.print_hello_world,
+				[ExampleExtension_translate('menu', 'Fire Rust Event', 'Fire Rust Event')]: window.ExampleExtension_fns.fire_rust_event,
+				[ExampleExtension_translate('menu', 'Print Extension Name', 'Print Extension Name')]: window.ExampleExtension_fns.print_extension_name,
 
             };
         }
@@ -56,6 +429,7 @@
 					'control',
 					[
 						new Extension.Palette.Block('receiveTestEvent'),
+						new Extension.Palette.Block('onRustEvent'),
 						new Extension.Palette.Block('printProcess'),
 						'-',
 						new Extension.Palette.Block('explode'),
@@ -67,6 +441,8 @@
 						new Extension.Palette.Block('falliblePredicate'),
 						'-',
 						new Extension.Palette.Block('pickyboi'),
+						new Extension.Palette.Block('repeatLogged'),
+						new Extension.Palette.Block('forEachValue'),
 					],
 					SpriteMorph
 				),
@@ -74,6 +450,7 @@
 					'control',
 					[
 						new Extension.Palette.Block('receiveTestEvent'),
+						new Extension.Palette.Block('onRustEvent'),
 						new Extension.Palette.Block('printProcess'),
 						'-',
 						new Extension.Palette.Block('explode'),
@@ -85,6 +462,67 @@
 						new Extension.Palette.Block('falliblePredicate'),
 						'-',
 						new Extension.Palette.Block('pickyboi'),
+						new Extension.Palette.Block('repeatLogged'),
+						new Extension.Palette.Block('forEachValue'),
+					],
+					StageMorph
+				),
+				new Extension.PaletteCategory(
+					'lists',
+					[
+						new Extension.Palette.Block('pointBounds'),
+						new Extension.Palette.Block('movingAverage'),
+					],
+					SpriteMorph
+				),
+				new Extension.PaletteCategory(
+					'lists',
+					[
+						new Extension.Palette.Block('pointBounds'),
+						new Extension.Palette.Block('movingAverage'),
+					],
+					StageMorph
+				),
+				new Extension.PaletteCategory(
+					'looks',
+					[
+						new Extension.Palette.Block('invertCostume'),
+					],
+					SpriteMorph
+				),
+				new Extension.PaletteCategory(
+					'looks',
+					[
+						new Extension.Palette.Block('invertCostume'),
+					],
+					StageMorph
+				),
+				new Extension.PaletteCategory(
+					'motion',
+					[
+						new Extension.Palette.Block('jumpAndSay'),
+					],
+					SpriteMorph
+				),
+				new Extension.PaletteCategory(
+					'motion',
+					[
+					],
+					StageMorph
+				),
+				new Extension.PaletteCategory(
+					'network',
+					[
+						new Extension.Palette.Block('sendChat'),
+						new Extension.Palette.Block('rustTemperature'),
+					],
+					SpriteMorph
+				),
+				new Extension.PaletteCategory(
+					'network',
+					[
+						new Extension.Palette.Block('sendChat'),
+						new Extension.Palette.Block('rustTemperature'),
 					],
 					StageMorph
 				),
@@ -92,9 +530,17 @@
 					'operators',
 					[
 						new Extension.Palette.Block('repeatString'),
+						new Extension.Palette.Block('jsUppercase'),
 						new Extension.Palette.Block('isEven'),
 						new Extension.Palette.Block('addAll'),
 						new Extension.Palette.Block('defaultAdder'),
+						new Extension.Palette.Block('newTally'),
+						new Extension.Palette.Block('addToTally'),
+						new Extension.Palette.Block('countMatching'),
+						new Extension.Palette.Block('scaler'),
+						new Extension.Palette.Block('countPrimes'),
+						new Extension.Palette.Block('nthPrime'),
+						new Extension.Palette.Block('xorBytes'),
 					],
 					SpriteMorph
 				),
@@ -102,9 +548,77 @@
 					'operators',
 					[
 						new Extension.Palette.Block('repeatString'),
+						new Extension.Palette.Block('jsUppercase'),
 						new Extension.Palette.Block('isEven'),
 						new Extension.Palette.Block('addAll'),
 						new Extension.Palette.Block('defaultAdder'),
+						new Extension.Palette.Block('newTally'),
+						new Extension.Palette.Block('addToTally'),
+						new Extension.Palette.Block('countMatching'),
+						new Extension.Palette.Block('scaler'),
+						new Extension.Palette.Block('countPrimes'),
+						new Extension.Palette.Block('nthPrime'),
+						new Extension.Palette.Block('xorBytes'),
+					],
+					StageMorph
+				),
+				new Extension.PaletteCategory(
+					'pen',
+					[
+						new Extension.Palette.Block('drawPolygon'),
+					],
+					SpriteMorph
+				),
+				new Extension.PaletteCategory(
+					'pen',
+					[
+						new Extension.Palette.Block('drawPolygon'),
+					],
+					StageMorph
+				),
+				new Extension.PaletteCategory(
+					'sensing',
+					[
+						new Extension.Palette.Block('countMyCalls'),
+					],
+					SpriteMorph
+				),
+				new Extension.PaletteCategory(
+					'sensing',
+					[
+						new Extension.Palette.Block('countMyCalls'),
+					],
+					StageMorph
+				),
+				new Extension.PaletteCategory(
+					'sound',
+					[
+						new Extension.Palette.Block('synthesizeTone'),
+						new Extension.Palette.Block('peakAmplitude'),
+					],
+					SpriteMorph
+				),
+				new Extension.PaletteCategory(
+					'sound',
+					[
+						new Extension.Palette.Block('synthesizeTone'),
+						new Extension.Palette.Block('peakAmplitude'),
+					],
+					StageMorph
+				),
+				new Extension.PaletteCategory(
+					'variables',
+					[
+						new Extension.Palette.Block('remember'),
+						new Extension.Palette.Block('remembered'),
+					],
+					SpriteMorph
+				),
+				new Extension.PaletteCategory(
+					'variables',
+					[
+						new Extension.Palette.Block('remember'),
+						new Extension.Palette.Block('remembered'),
 					],
 					StageMorph
 				),
@@ -118,113 +632,297 @@
 					'logHelloWorld',
 					'command',
 					'Hello World',
-					'Log Hello World!',
-					[],
+					...ExampleExtension_localizeBlock('logHelloWorld', 'Log Hello World!', [],
 					function () { return window.ExampleExtension_fns.hello_world(); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'logHelloName',
 					'command',
 					'Hello World',
-					'Log Hello %s',
-					[],
+					...ExampleExtension_localizeBlock('logHelloName', 'Log Hello %s', [],
 					function (v0) { return window.ExampleExtension_fns.hello_name(v0); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'repeatString',
 					'reporter',
 					'operators',
-					'Repeat %s for %times times',
-					[],
+					...ExampleExtension_localizeBlock('repeatString', 'Repeat %s for %times times', [],
 					function (v0, v1) { return window.ExampleExtension_fns.repeat_text(v0, v1); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'jsUppercase',
+					'reporter',
+					'operators',
+					...ExampleExtension_localizeBlock('jsUppercase', 'uppercase %s', [],
+					function (text) { return text.toString().toUpperCase(); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'isEven',
 					'predicate',
 					'operators',
-					'is %num even?',
-					[],
+					...ExampleExtension_localizeBlock('isEven', 'is %num even?', [],
 					function (v0) { return window.ExampleExtension_fns.is_even(v0); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'receiveTestEvent',
 					'hat',
 					'control',
-					'on test event',
-					[],
+					...ExampleExtension_localizeBlock('receiveTestEvent', 'on test event', [],
 					function () { return window.ExampleExtension_fns.receive_test_event(); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'onRustEvent',
+					'hat',
+					'control',
+					...ExampleExtension_localizeBlock('onRustEvent', 'when Rust event %upvar', ['data'],
+					function (v0) { return window.ExampleExtension_fns.on_rust_event({ upvar: v0, variables: this.context.outerContext.variables }); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'printProcess',
 					'command',
 					'control',
-					'print process',
-					[],
+					...ExampleExtension_localizeBlock('printProcess', 'print process', [],
 					function () { return window.ExampleExtension_fns.print_process(this, ); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'explode',
 					'command',
 					'control',
-					'explode',
-					[],
+					...ExampleExtension_localizeBlock('explode', 'explode', [],
 					function () { return window.ExampleExtension_fns.explode(); }
+					)
 				).terminal().for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'addAll',
 					'reporter',
 					'operators',
-					'add numbers %mult%num',
-					[],
+					...ExampleExtension_localizeBlock('addAll', 'add numbers %mult%num', [],
 					function (v0) { return window.ExampleExtension_fns.add_all(v0.contents); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'explicitCommand',
 					'command',
 					'control',
-					'explicit command',
-					[],
+					...ExampleExtension_localizeBlock('explicitCommand', 'explicit command', [],
 					function () { return window.ExampleExtension_fns.explicit_command(); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'fallibleCommand',
 					'command',
 					'control',
-					'fallible command',
-					[],
+					...ExampleExtension_localizeBlock('fallibleCommand', 'fallible command', [],
 					function () { return window.ExampleExtension_fns.fallible_command(); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'fallibleReporter',
 					'reporter',
 					'control',
-					'fallible reporter',
-					[],
+					...ExampleExtension_localizeBlock('fallibleReporter', 'fallible reporter', [],
 					function () { return window.ExampleExtension_fns.fallible_reporter(); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'falliblePredicate',
 					'predicate',
 					'control',
-					'fallible predicate',
-					[],
+					...ExampleExtension_localizeBlock('falliblePredicate', 'fallible predicate', [],
 					function () { return window.ExampleExtension_fns.fallible_predicate(); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'pickyboi',
 					'reporter',
 					'control',
-					'picky boi %picky',
-					[],
+					...ExampleExtension_localizeBlock('pickyboi', 'picky boi %picky', [],
 					function (v0) { return window.ExampleExtension_fns.picky_boi(v0); }
+					)
 				).for(SpriteMorph, StageMorph),
 				new Extension.Block(
 					'defaultAdder',
 					'reporter',
 					'operators',
-					'add %n + %n',
-					['7', '-4'],
+					...ExampleExtension_localizeBlock('defaultAdder', 'add %n + %n', ['7', '-4'],
 					function (v0, v1) { return window.ExampleExtension_fns.default_adder(v0, v1); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'newTally',
+					'reporter',
+					'operators',
+					...ExampleExtension_localizeBlock('newTally', 'new tally', [],
+					function () { return window.ExampleExtension_fns.new_tally(); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'addToTally',
+					'reporter',
+					'operators',
+					...ExampleExtension_localizeBlock('addToTally', 'add %n to tally %s', [],
+					function (v0, v1) { return window.ExampleExtension_fns.add_to_tally(v0, v1); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'countMatching',
+					'reporter',
+					'operators',
+					...ExampleExtension_localizeBlock('countMatching', 'count %mult%n matching %predRing', [],
+					function (v0, v1) { return ExampleExtension_await(this, token => window.ExampleExtension_fns.count_matching(v0.contents, { ring: v1, process: this })); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'repeatLogged',
+					'command',
+					'control',
+					...ExampleExtension_localizeBlock('repeatLogged', 'repeat %n times logged %cs', [],
+					function (v0, v1) { return ExampleExtension_await(this, token => window.ExampleExtension_fns.repeat_logged(v0, { ring: ExampleExtension_script(this, v1), process: this })); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'forEachValue',
+					'command',
+					'control',
+					...ExampleExtension_localizeBlock('forEachValue', 'for each %upvar in %mult%s %cs', ['value'],
+					function (v0, v1, v2) { return ExampleExtension_await(this, token => window.ExampleExtension_fns.for_each_value({ upvar: v0, variables: this.context.outerContext.variables }, v1.contents, { ring: ExampleExtension_script(this, v2), process: this })); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'scaler',
+					'reporter',
+					'operators',
+					...ExampleExtension_localizeBlock('scaler', 'scale by %n', [],
+					function (v0) { return window.ExampleExtension_fns.scaler(v0); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'sendChat',
+					'command',
+					'network',
+					...ExampleExtension_localizeBlock('sendChat', 'send chat %s to %s', ['hello', 'everyone in room'],
+					function (v0, v1) { return window.ExampleExtension_fns.send_chat(v0, v1); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'rustTemperature',
+					'reporter',
+					'network',
+					...ExampleExtension_localizeBlock('rustTemperature', 'temperature at lat %n long %n', [],
+					function (v0, v1) { return ExampleExtension_await(this, token => window.ExampleExtension_fns.rust_temperature(v0, v1)); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'jumpAndSay',
+					'command',
+					'motion',
+					...ExampleExtension_localizeBlock('jumpAndSay', 'jump somewhere and say %s', [],
+					function (v0) { return window.ExampleExtension_fns.jump_and_say(this, v0); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'invertCostume',
+					'reporter',
+					'looks',
+					...ExampleExtension_localizeBlock('invertCostume', 'invert %img', [],
+					function (v0) { return window.ExampleExtension_fns.invert_costume(v0); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'synthesizeTone',
+					'reporter',
+					'sound',
+					...ExampleExtension_localizeBlock('synthesizeTone', 'tone %n Hz for %n secs', [440, 1],
+					function (v0, v1) { return window.ExampleExtension_fns.synthesize_tone(v0, v1); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'peakAmplitude',
+					'reporter',
+					'sound',
+					...ExampleExtension_localizeBlock('peakAmplitude', 'peak amplitude of %snd', [],
+					function (v0) { return ExampleExtension_await(this, token => window.ExampleExtension_fns.peak_amplitude(v0)); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'drawPolygon',
+					'command',
+					'pen',
+					...ExampleExtension_localizeBlock('drawPolygon', 'draw polygon with %n sides radius %n', [6, 100],
+					function (v0, v1) { return window.ExampleExtension_fns.draw_polygon(this, v0, v1); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'countPrimes',
+					'reporter',
+					'operators',
+					...ExampleExtension_localizeBlock('countPrimes', 'number of primes below %n', [1000000],
+					function (v0) { return ExampleExtension_await(this, token => window.ExampleExtension_fns.count_primes(this, token, v0), 60000); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'nthPrime',
+					'reporter',
+					'operators',
+					...ExampleExtension_localizeBlock('nthPrime', 'prime number %n', [10000],
+					function (v0) { return ExampleExtension_await(this, token => ExampleExtension_worker('nth_prime', [v0], token)); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'countMyCalls',
+					'reporter',
+					'sensing',
+					...ExampleExtension_localizeBlock('countMyCalls', 'my call count', [],
+					function () { return window.ExampleExtension_fns.count_my_calls(this, ); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'remember',
+					'command',
+					'variables',
+					...ExampleExtension_localizeBlock('remember', 'remember %s', [],
+					function (v0) { return window.ExampleExtension_fns.remember(v0); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'remembered',
+					'reporter',
+					'variables',
+					...ExampleExtension_localizeBlock('remembered', 'remembered text', [],
+					function () { return window.ExampleExtension_fns.remembered(); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'pointBounds',
+					'reporter',
+					'lists',
+					...ExampleExtension_localizeBlock('pointBounds', 'bounds of points %l', [],
+					function (v0) { return window.ExampleExtension_fns.point_bounds(v0); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'xorBytes',
+					'reporter',
+					'operators',
+					...ExampleExtension_localizeBlock('xorBytes', 'xor bytes %s with %n', ['aGVsbG8=', 42],
+					function (v0, v1) { return ExampleExtension_fromArray(window.ExampleExtension_fns.xor_bytes(ExampleExtension_toBytes(v0), v1)); }
+					)
+				).for(SpriteMorph, StageMorph),
+				new Extension.Block(
+					'movingAverage',
+					'reporter',
+					'lists',
+					...ExampleExtension_localizeBlock('movingAverage', 'moving average of %l over %n', [null, 3],
+					function (v0, v1) { return ExampleExtension_fromArray(window.ExampleExtension_fns.moving_average(ExampleExtension_toNumbers(v0), v1)); }
+					)
 				).for(SpriteMorph, StageMorph),
 
             ];
@@ -272,35 +970,100 @@
             ];
         }
 
+
     }
 
+    // Show help text for this extension's blocks, deferring to NetsBlox for all others
+    const ExampleExtension_help = {
+		'logHelloName': "Logs a greeting for the given name to the browser console.",
+		'repeatString': "Repeats the text the given number of times.\n\nFractional counts are rounded down.",
+		'defaultAdder': "Adds two numbers, starting with 7 and -4.",
+		'addToTally': "Adds to a tally created by the \"new tally\" block and reports the new total.",
+		'countMatching': "Counts how many of the numbers the predicate reports true for.",
+		'repeatLogged': "Runs the enclosed script the given number of times, logging each iteration.",
+		'forEachValue': "Runs the enclosed script once for each value, with the upvar set to the value.",
+		'scaler': "Reports a ring that scales numbers by the given factor.",
+		'sendChat': "Sends a chat message with the given text to a role.",
+		'rustTemperature': "Reports the current temperature at a location from the Weather service.",
+		'jumpAndSay': "Moves the sprite to a random position on the stage and says the given text.",
+		'invertCostume': "Reports a copy of the costume with its colors inverted.",
+		'synthesizeTone': "Reports a sine tone with the given frequency and duration.",
+		'peakAmplitude': "Reports the largest absolute sample value of a sound.",
+		'drawPolygon': "Draws a regular polygon centered on the stage with the pen trails.",
+		'countPrimes': "Counts the primes below a limit, yielding regularly so the IDE stays responsive and giving up after a minute.",
+		'nthPrime': "Reports the nth prime number, computed on a Web Worker.",
+		'countMyCalls': "Counts how many times the sprite running the block has used it. Clones count separately.",
+		'remember': "Remembers text, which is saved with the project.",
+		'remembered': "Reports the text given to the remember block.",
+		'pointBounds': "Reports the bounds of a list of [x, y] points as a list of [key, value] pairs.",
+		'xorBytes': "XORs every byte of a list of bytes or base64 text with a key from 0 to 255.",
+		'movingAverage': "Reports the moving average of a list of numbers over windows of the given size.",
+
+    };
+    const ExampleExtension_showHelp = BlockMorph.prototype.showHelp;
+    BlockMorph.prototype.showHelp = function () {
+        const help = ExampleExtension_help[this.selector];
+        if (help === undefined) {
+            return ExampleExtension_showHelp.apply(this, arguments);
+        }
+        new DialogBoxMorph().inform('Help', help, this.world(), this.fullImage());
+    };
+
     NetsBloxExtensions.register(ExampleExtension);
     let path = document.currentScript.src;
     path = path.substring(0, path.lastIndexOf("/"));
     var s = document.createElement('script');
     s.type = "module";
-    s.innerHTML = `import init, {add_all, default_adder, explicit_command, explode, fallible_command, fallible_predicate, fallible_reporter, hello_name, hello_world, is_even, picky_boi, print_extension_name, print_hello_world, print_process, receive_test_event, repeat_text} from '${path}/pkg/netsblox_extension_rs.js';
+    s.innerHTML = `import init, {add_all, add_to_tally, count_matching, count_my_calls, count_primes, default_adder, draw_polygon, explicit_command, explode, fallible_command, fallible_predicate, fallible_reporter, fire_rust_event, for_each_value, hello_name, hello_world, invert_costume, is_even, jump_and_say, moving_average, netsblox_extension_load_state, netsblox_extension_open_role, netsblox_extension_save_state, new_tally, nth_prime, on_rust_event, peak_amplitude, picky_boi, point_bounds, print_extension_name, print_hello_world, print_process, receive_test_event, remember, remembered, repeat_logged, repeat_text, rust_temperature, scaler, send_chat, synthesize_tone, xor_bytes} from '${path}/pkg/netsblox_extension_rs.js';
     
     
         await init();
 
         window.ExampleExtension_fns = {};
 		window.ExampleExtension_fns.add_all = add_all;
+		window.ExampleExtension_fns.add_to_tally = add_to_tally;
+		window.ExampleExtension_fns.count_matching = count_matching;
+		window.ExampleExtension_fns.count_my_calls = count_my_calls;
+		window.ExampleExtension_fns.count_primes = count_primes;
 		window.ExampleExtension_fns.default_adder = default_adder;
+		window.ExampleExtension_fns.draw_polygon = draw_polygon;
 		window.ExampleExtension_fns.explicit_command = explicit_command;
 		window.ExampleExtension_fns.explode = explode;
 		window.ExampleExtension_fns.fallible_command = fallible_command;
 		window.ExampleExtension_fns.fallible_predicate = fallible_predicate;
 		window.ExampleExtension_fns.fallible_reporter = fallible_reporter;
+		window.ExampleExtension_fns.fire_rust_event = fire_rust_event;
+		window.ExampleExtension_fns.for_each_value = for_each_value;
 		window.ExampleExtension_fns.hello_name = hello_name;
 		window.ExampleExtension_fns.hello_world = hello_world;
+		window.ExampleExtension_fns.invert_costume = invert_costume;
 		window.ExampleExtension_fns.is_even = is_even;
+		window.ExampleExtension_fns.jump_and_say = jump_and_say;
+		window.ExampleExtension_fns.moving_average = moving_average;
+		window.ExampleExtension_fns.netsblox_extension_load_state = netsblox_extension_load_state;
+		window.ExampleExtension_fns.netsblox_extension_open_role = netsblox_extension_open_role;
+		window.ExampleExtension_fns.netsblox_extension_save_state = netsblox_extension_save_state;
+		window.ExampleExtension_fns.new_tally = new_tally;
+		window.ExampleExtension_fns.nth_prime = nth_prime;
+		window.ExampleExtension_fns.on_rust_event = on_rust_event;
+		window.ExampleExtension_fns.peak_amplitude = peak_amplitude;
 		window.ExampleExtension_fns.picky_boi = picky_boi;
+		window.ExampleExtension_fns.point_bounds = point_bounds;
 		window.ExampleExtension_fns.print_extension_name = print_extension_name;
 		window.ExampleExtension_fns.print_hello_world = print_hello_world;
 		window.ExampleExtension_fns.print_process = print_process;
 		window.ExampleExtension_fns.receive_test_event = receive_test_event;
+		window.ExampleExtension_fns.remember = remember;
+		window.ExampleExtension_fns.remembered = remembered;
+		window.ExampleExtension_fns.repeat_logged = repeat_logged;
 		window.ExampleExtension_fns.repeat_text = repeat_text;
+		window.ExampleExtension_fns.rust_temperature = rust_temperature;
+		window.ExampleExtension_fns.scaler = scaler;
+		window.ExampleExtension_fns.send_chat = send_chat;
+		window.ExampleExtension_fns.synthesize_tone = synthesize_tone;
+		window.ExampleExtension_fns.xor_bytes = xor_bytes;
+        window.ExampleExtension_initialized();
+
         `;
     document.body.appendChild(s);
 })();
\ No newline at end of file
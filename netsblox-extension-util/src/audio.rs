@@ -0,0 +1,266 @@
+use std::fmt;
+
+use js_sys::{Array, Float32Array, Promise, Reflect};
+use wasm_bindgen::{convert::IntoWasmAbi, describe::WasmDescribe, prelude::*};
+use wasm_bindgen_futures::JsFuture;
+
+#[wasm_bindgen(inline_js = r#"
+export async function decodeSound(sound) {
+    if (!(sound instanceof Sound)) {
+        throw new Error('expected a sound');
+    }
+    // Cached on the sound the same way NetsBlox's own audio blocks do
+    if (!sound.audioBuffer) {
+        const data = await (await fetch(sound.audio.src)).arrayBuffer();
+        sound.audioBuffer = await Note.prototype.getAudioContext().decodeAudioData(data);
+    }
+    const buffer = sound.audioBuffer;
+    const channels = [];
+    for (let i = 0; i < buffer.numberOfChannels; i++) {
+        channels.push(buffer.getChannelData(i));
+    }
+    return { sampleRate: buffer.sampleRate, name: sound.name, channels };
+}
+
+export function makeSound(wav, name) {
+    // A data URL, so the sound is saved with the project like recorded sounds
+    let binary = '';
+    for (let i = 0; i < wav.length; i += 0x8000) {
+        binary += String.fromCharCode.apply(null, wav.subarray(i, i + 0x8000));
+    }
+    return new Sound(new Audio('data:audio/wav;base64,' + btoa(binary)), name);
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = decodeSound)]
+    fn decode_sound(sound: &JsValue) -> Promise;
+    #[wasm_bindgen(js_name = makeSound)]
+    fn make_sound(wav: &[u8], name: &str) -> JsValue;
+}
+
+/// Error returned when audio cannot be created or decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioError {
+    /// The value passed to a block is not a sound, or the browser could not decode it
+    NotASound(String),
+    /// There are no channels, channels differ in length, or the sample rate is 0
+    InvalidChannels,
+    /// There are too many samples or channels, or the sample rate is too high, to encode as a WAV file
+    TooLarge,
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::NotASound(e) => write!(f, "could not decode sound: {e}"),
+            AudioError::InvalidChannels => write!(f, "audio needs a sample rate and channels of equal length"),
+            AudioError::TooLarge => write!(f, "audio is too large to make a sound from"),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+impl From<AudioError> for JsValue {
+    fn from(e: AudioError) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+/// PCM audio with samples from -1 to 1, one buffer per channel.
+///
+/// Returning `Audio` from a block gives students a sound they can play or store in a list.
+/// Sounds passed to `%snd` slots are decoded with [`Audio::from_js`], which needs an async block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audio {
+    sample_rate: u32,
+    channels: Vec<Vec<f32>>,
+    name: String,
+}
+
+// Fields of a 16-bit PCM WAV header, which overflow for very large audio
+struct WavSizes {
+    channels: u16,
+    block_align: u16,
+    byte_rate: u32,
+    data_len: u32,
+}
+
+impl WavSizes {
+    fn new(sample_rate: u32, channels: usize, samples: usize) -> Option<Self> {
+        let channels = u16::try_from(channels).ok()?;
+        let block_align = channels.checked_mul(2)?;
+        let byte_rate = sample_rate.checked_mul(block_align as u32)?;
+        let data_len = u32::try_from(samples.checked_mul(block_align as usize)?).ok()?;
+        data_len.checked_add(36)?;
+        Some(Self { channels, block_align, byte_rate, data_len })
+    }
+}
+
+impl Audio {
+    pub fn new(sample_rate: u32, channels: Vec<Vec<f32>>) -> Result<Self, AudioError> {
+        let len = channels.first().map(Vec::len);
+        if sample_rate == 0 || len.is_none() || channels.iter().any(|x| Some(x.len()) != len) {
+            return Err(AudioError::InvalidChannels);
+        }
+        // Checked up front so sounds can always be made from the audio
+        if WavSizes::new(sample_rate, channels.len(), channels[0].len()).is_none() {
+            return Err(AudioError::TooLarge);
+        }
+        Ok(Self { sample_rate, channels, name: "sound".into() })
+    }
+
+    pub fn mono(sample_rate: u32, samples: Vec<f32>) -> Result<Self, AudioError> {
+        Self::new(sample_rate, vec![samples])
+    }
+
+    /// Set the name of the sound created from this audio
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> &[Vec<f32>] {
+        &self.channels
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f64 {
+        self.channels[0].len() as f64 / self.sample_rate as f64
+    }
+
+    /// The average of all channels
+    pub fn mixed(&self) -> Vec<f32> {
+        let count = self.channels.len() as f32;
+        (0..self.channels[0].len()).map(|i| self.channels.iter().map(|x| x[i]).sum::<f32>() / count).collect()
+    }
+
+    /// Encode as a 16-bit PCM WAV file
+    pub fn to_wav(&self) -> Result<Vec<u8>, AudioError> {
+        let sizes = WavSizes::new(self.sample_rate, self.channels.len(), self.channels[0].len()).ok_or(AudioError::TooLarge)?;
+
+        let mut res = Vec::with_capacity(44 + sizes.data_len as usize);
+        res.extend_from_slice(b"RIFF");
+        res.extend_from_slice(&(36 + sizes.data_len).to_le_bytes());
+        res.extend_from_slice(b"WAVEfmt ");
+        res.extend_from_slice(&16u32.to_le_bytes());
+        res.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        res.extend_from_slice(&sizes.channels.to_le_bytes());
+        res.extend_from_slice(&self.sample_rate.to_le_bytes());
+        res.extend_from_slice(&sizes.byte_rate.to_le_bytes());
+        res.extend_from_slice(&sizes.block_align.to_le_bytes());
+        res.extend_from_slice(&16u16.to_le_bytes());
+        res.extend_from_slice(b"data");
+        res.extend_from_slice(&sizes.data_len.to_le_bytes());
+
+        for i in 0..self.channels[0].len() {
+            for channel in &self.channels {
+                res.extend_from_slice(&((channel[i].clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+            }
+        }
+        Ok(res)
+    }
+
+    /// Decode a sound passed in by NetsBlox
+    pub async fn from_js(sound: &JsValue) -> Result<Self, AudioError> {
+        let error = |e: JsValue| AudioError::NotASound(e.as_string().or_else(|| Reflect::get(&e, &"message".into()).ok()?.as_string()).unwrap_or_default());
+        let res = JsFuture::from(decode_sound(sound)).await.map_err(error)?;
+        let sample_rate = Reflect::get(&res, &"sampleRate".into()).map_err(error)?.as_f64().unwrap_or_default() as u32;
+        let name = Reflect::get(&res, &"name".into()).map_err(error)?.as_string().unwrap_or_default();
+        let channels: Array = Reflect::get(&res, &"channels".into()).map_err(error)?.unchecked_into();
+        let channels = channels.iter().map(|x| Float32Array::new(&x).to_vec()).collect();
+        Ok(Self::new(sample_rate, channels)?.with_name(name))
+    }
+
+    /// Create the sound NetsBlox stores for this audio
+    pub fn to_js(&self) -> JsValue {
+        make_sound(&self.to_wav().expect("audio size is checked when it is created"), &self.name)
+    }
+}
+
+impl From<Audio> for JsValue {
+    fn from(audio: Audio) -> Self {
+        audio.to_js()
+    }
+}
+
+impl WasmDescribe for Audio {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl IntoWasmAbi for Audio {
+    type Abi = <JsValue as IntoWasmAbi>::Abi;
+
+    fn into_abi(self) -> Self::Abi {
+        self.to_js().into_abi()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(wav: &[u8], i: usize) -> u16 {
+        u16::from_le_bytes(wav[i..i + 2].try_into().unwrap())
+    }
+
+    fn u32_at(wav: &[u8], i: usize) -> u32 {
+        u32::from_le_bytes(wav[i..i + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn wav_round_trip() {
+        let audio = Audio::new(8000, vec![vec![0.0, 0.5, -1.0], vec![1.0, 2.0, -0.25]]).unwrap();
+        let wav = audio.to_wav().unwrap();
+
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(&wav, 22), 2);
+        assert_eq!(u32_at(&wav, 24), 8000);
+        assert_eq!(u32_at(&wav, 28), 8000 * 4);
+        assert_eq!(u16_at(&wav, 32), 4);
+        assert_eq!(u16_at(&wav, 34), 16);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40), 12);
+
+        // Interleaved by frame, with samples out of range clamped
+        let samples = wav[44..].chunks_exact(2).map(|x| i16::from_le_bytes([x[0], x[1]]) as f32 / i16::MAX as f32).collect::<Vec<_>>();
+        let expected = [0.0, 1.0, 0.5, 1.0, -1.0, -0.25];
+        assert_eq!(samples.len(), expected.len());
+        for (x, y) in samples.iter().zip(expected) {
+            assert!((x - y).abs() < 1e-4, "{samples:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_audio_is_rejected() {
+        assert_eq!(Audio::new(0, vec![vec![0.0]]), Err(AudioError::InvalidChannels));
+        assert_eq!(Audio::new(44100, vec![]), Err(AudioError::InvalidChannels));
+        assert_eq!(Audio::new(44100, vec![vec![0.0], vec![]]), Err(AudioError::InvalidChannels));
+        assert_eq!(Audio::mono(u32::MAX, vec![0.0]), Err(AudioError::TooLarge));
+    }
+
+    #[test]
+    fn wav_sizes_do_not_overflow() {
+        assert!(WavSizes::new(44100, 2, 1000).is_some());
+        assert!(WavSizes::new(44100, 1, (u32::MAX as usize - 36) / 2).is_some());
+        assert!(WavSizes::new(44100, 1, u32::MAX as usize / 2).is_none());
+        assert!(WavSizes::new(44100, 40000, 1).is_none());
+        assert!(WavSizes::new(44100, 70000, 1).is_none());
+    }
+}
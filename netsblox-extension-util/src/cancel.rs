@@ -0,0 +1,121 @@
+use std::fmt;
+#[cfg(feature = "testing")]
+use std::{cell::Cell, rc::Rc};
+
+use js_sys::{Promise, Reflect};
+use wasm_bindgen::{convert::FromWasmAbi, describe::WasmDescribe, prelude::*};
+use wasm_bindgen_futures::JsFuture;
+
+/// Why a block was cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    /// The script running the block was stopped, e.g. with the stop button
+    Stopped,
+    /// The block ran longer than its `timeout_ms`
+    Timeout,
+}
+
+/// Error returned by [`CancellationToken::check`] once a block is cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled {
+    pub reason: CancelReason,
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            CancelReason::Stopped => write!(f, "block was stopped"),
+            CancelReason::Timeout => write!(f, "block timed out"),
+        }
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl From<Cancelled> for JsValue {
+    fn from(e: Cancelled) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+/// Tells an async block that it should stop, because its script was stopped or it ran longer than its `timeout_ms`.
+///
+/// Async blocks receive one by taking a `CancellationToken` as their first parameter after the process, if any.
+/// When cancelled, [`Process::yield_now`](crate::Process::yield_now) completes once more so the block can notice.
+pub struct CancellationToken {
+    pub(crate) token: JsValue,
+    #[cfg(feature = "testing")]
+    pub(crate) mock: Option<Rc<Cell<Option<CancelReason>>>>,
+}
+
+impl Clone for CancellationToken {
+    fn clone(&self) -> Self {
+        mocked!(self, mock => CancellationToken::mock(mock.clone()));
+        CancellationToken::from_js(self.token.clone())
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        mocked!(self, mock => f.debug_tuple("CancellationToken").field(&mock.get()).finish());
+        f.debug_tuple("CancellationToken").field(&self.token).finish()
+    }
+}
+
+impl CancellationToken {
+    /// Recover a token from the value the generated extension passes to async blocks
+    pub fn from_js(value: JsValue) -> Self {
+        Self {
+            token: value,
+            #[cfg(feature = "testing")]
+            mock: None,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    pub fn reason(&self) -> Option<CancelReason> {
+        mocked!(self, mock => mock.get());
+        match Reflect::get(&self.token, &"reason".into()).ok()?.as_string()?.as_str() {
+            "stopped" => Some(CancelReason::Stopped),
+            "timeout" => Some(CancelReason::Timeout),
+            _ => None,
+        }
+    }
+
+    /// Fail with [`Cancelled`] once the block is cancelled, for use with `?` in long loops
+    pub fn check(&self) -> Result<(), Cancelled> {
+        match self.reason() {
+            Some(reason) => Err(Cancelled { reason }),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait until the block is cancelled
+    pub async fn cancelled(&self) -> CancelReason {
+        mocked!(self, mock => match mock.get() {
+            Some(reason) => reason,
+            None => std::future::pending().await,
+        });
+        if let Ok(promise) = Reflect::get(&self.token, &"promise".into()).and_then(|x| x.dyn_into::<Promise>()) {
+            let _ = JsFuture::from(promise).await;
+        }
+        self.reason().unwrap_or(CancelReason::Stopped)
+    }
+}
+
+impl WasmDescribe for CancellationToken {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl FromWasmAbi for CancellationToken {
+    type Abi = <JsValue as FromWasmAbi>::Abi;
+
+    unsafe fn from_abi(js: Self::Abi) -> Self {
+        CancellationToken::from_js(JsValue::from_abi(js))
+    }
+}
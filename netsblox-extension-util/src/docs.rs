@@ -0,0 +1,292 @@
+use std::{collections::BTreeMap, fmt::Write as FmtWrite};
+
+use crate::{ExtensionManifest, ManifestBlock, Menu, Slot, TargetObject};
+
+/// Formats block documentation can be generated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    /// Written to extension.md
+    Markdown,
+    /// Written to extension.html
+    Html,
+}
+
+impl DocFormat {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            DocFormat::Markdown => "extension.md",
+            DocFormat::Html => "extension.html",
+        }
+    }
+}
+
+// Names for the most common built-in slot types
+fn builtin_slot_type(label_part: &str) -> Option<&'static str> {
+    Some(match label_part {
+        "s" | "txt" | "mlt" | "code" => "text",
+        "n" => "number",
+        "b" | "bool" | "boolUE" => "boolean",
+        "l" | "list" => "list",
+        "anyUE" => "any (unevaluated)",
+        "cmdRing" => "command ring",
+        "repRing" => "reporter ring",
+        "predRing" => "predicate ring",
+        "c" | "cs" | "ca" => "command script",
+        "upvar" => "variable name",
+        "img" => "costume",
+        "snd" => "sound",
+        "spr" => "sprite",
+        _ => return None,
+    })
+}
+
+fn slot_type(slot: &Slot) -> String {
+    let ty = match (slot.custom, builtin_slot_type(slot.label_part)) {
+        (Some(part), _) => {
+            let base = if part.numeric { "number" } else { "text" };
+            if part.readonly { format!("{base} (read-only)") } else { base.to_string() }
+        }
+        (None, Some(ty)) => ty.to_string(),
+        (None, None) => format!("%{}", slot.label_part),
+    };
+
+    if slot.variadic { format!("{ty}, variadic") } else { ty }
+}
+
+// Flatten nested menus into "submenu > entry" labels
+fn slot_options(slot: &Slot) -> Vec<String> {
+    fn visitor(menu: &Menu, prefix: &str, res: &mut Vec<String>) {
+        match menu {
+            Menu::Entry { label, value } if label == value => res.push(format!("{prefix}{label}")),
+            Menu::Entry { label, value } => res.push(format!("{prefix}{label} ({value})")),
+            Menu::Submenu { label, content } => {
+                for x in *content {
+                    visitor(x, &format!("{prefix}{label} > "), res);
+                }
+            }
+        }
+    }
+
+    let mut res = vec![];
+    for x in slot.custom.and_then(|x| x.menu).unwrap_or_default() {
+        visitor(x, "", &mut res);
+    }
+    res
+}
+
+// Defaults are a JS array literal, split it into its top level values
+pub(crate) fn parse_defaults(defaults: &str) -> Vec<String> {
+    let inner = defaults.trim().trim_start_matches('[').trim_end_matches(']');
+    let mut res = vec![];
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut depth = 0;
+
+    for c in inner.chars() {
+        match (c, quote) {
+            (c, Some(_)) if escaped => { escaped = false; current.push(c); }
+            ('\\', Some(_)) => escaped = true,
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('[' | '{' | '(', None) => { depth += 1; current.push(c); }
+            (']' | '}' | ')', None) => { depth -= 1; current.push(c); }
+            (',', None) if depth == 0 => res.push(std::mem::take(&mut current).trim().to_string()),
+            (c, _) => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        res.push(current.trim().to_string());
+    }
+    res
+}
+
+fn targets(target: &TargetObject) -> &'static str {
+    match target {
+        TargetObject::SpriteMorph => "sprites",
+        TargetObject::StageMorph => "stage",
+        TargetObject::Both => "sprites and stage",
+    }
+}
+
+fn block_type_name(block: &ManifestBlock) -> String {
+    let name = serde_json::to_string(&block.block.block_type).unwrap().trim_matches('"').to_string();
+    if block.terminal { format!("{name} (terminal)") } else { name }
+}
+
+// Blocks grouped by category, in category name order
+fn blocks_by_category<'a, 'b>(manifest: &'b ExtensionManifest<'a>) -> BTreeMap<&'a str, Vec<&'b ManifestBlock<'a>>> {
+    let mut res: BTreeMap<&str, Vec<&ManifestBlock>> = BTreeMap::new();
+    for block in &manifest.blocks {
+        res.entry(block.block.category).or_default().push(block);
+    }
+    res
+}
+
+// Pipes would end the cell early
+fn escape_table_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+pub fn generate_markdown(manifest: &ExtensionManifest) -> String {
+    let mut res = String::new();
+    writeln!(res, "# {}\n", manifest.info.name).unwrap();
+
+    for (category, blocks) in blocks_by_category(manifest) {
+        writeln!(res, "## {category}\n").unwrap();
+
+        for block in blocks {
+            writeln!(res, "### {}\n", block.block.name).unwrap();
+            writeln!(res, "`{}`\n", block.block.spec).unwrap();
+            if let Some(doc) = block.block.doc {
+                writeln!(res, "{doc}\n").unwrap();
+            }
+
+            writeln!(res, "- Type: {}", block_type_name(block)).unwrap();
+            writeln!(res, "- Available for: {}", targets(&block.block.target)).unwrap();
+            if let Some(return_type) = block.block.return_type {
+                writeln!(res, "- Returns: `{return_type}`").unwrap();
+            }
+            res.push('\n');
+
+            if !block.slots.is_empty() {
+                let defaults = parse_defaults(block.block.defaults);
+                res.push_str("| Slot | Type | Default | Options |\n");
+                res.push_str("| --- | --- | --- | --- |\n");
+                for (i, slot) in block.slots.iter().enumerate() {
+                    let options = slot_options(slot).iter().map(|x| escape_table_cell(x)).collect::<Vec<_>>().join(", ");
+                    writeln!(res, "| %{} | {} | {} | {} |", slot.label_part, escape_table_cell(&slot_type(slot)), escape_table_cell(defaults.get(i).map(String::as_str).unwrap_or("")), options).unwrap();
+                }
+                res.push('\n');
+            }
+        }
+    }
+
+    res
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn generate_html(manifest: &ExtensionManifest) -> String {
+    let mut res = String::new();
+    let name = escape_html(manifest.info.name);
+    writeln!(res, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{name}</title>\n</head>\n<body>\n<h1>{name}</h1>").unwrap();
+
+    for (category, blocks) in blocks_by_category(manifest) {
+        writeln!(res, "<h2>{}</h2>", escape_html(category)).unwrap();
+
+        for block in blocks {
+            writeln!(res, "<h3 id=\"{0}\">{0}</h3>", escape_html(block.block.name)).unwrap();
+            writeln!(res, "<p><code>{}</code></p>", escape_html(block.block.spec)).unwrap();
+            if let Some(doc) = block.block.doc {
+                for paragraph in doc.split("\n\n") {
+                    writeln!(res, "<p>{}</p>", escape_html(paragraph)).unwrap();
+                }
+            }
+
+            res.push_str("<ul>\n");
+            writeln!(res, "<li>Type: {}</li>", block_type_name(block)).unwrap();
+            writeln!(res, "<li>Available for: {}</li>", targets(&block.block.target)).unwrap();
+            if let Some(return_type) = block.block.return_type {
+                writeln!(res, "<li>Returns: <code>{}</code></li>", escape_html(return_type)).unwrap();
+            }
+            res.push_str("</ul>\n");
+
+            if !block.slots.is_empty() {
+                let defaults = parse_defaults(block.block.defaults);
+                res.push_str("<table>\n<tr><th>Slot</th><th>Type</th><th>Default</th><th>Options</th></tr>\n");
+                for (i, slot) in block.slots.iter().enumerate() {
+                    let options = slot_options(slot).iter().map(|x| escape_html(x)).collect::<Vec<_>>().join(", ");
+                    writeln!(res, "<tr><td>%{}</td><td>{}</td><td>{}</td><td>{}</td></tr>", escape_html(slot.label_part), escape_html(&slot_type(slot)), escape_html(defaults.get(i).map(String::as_str).unwrap_or("")), options).unwrap();
+                }
+                res.push_str("</table>\n");
+            }
+        }
+    }
+
+    res.push_str("</body>\n</html>\n");
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{recreate_netsblox_extension_block, CustomBlock, ExtensionInfo, LabelPart};
+
+    const SEPARATORS: &[Menu] = &[
+        Menu::Entry { label: "pipe", value: "|" },
+        Menu::Submenu { label: "space", content: &[Menu::Entry { label: "tab", value: "\t" }] },
+    ];
+
+    fn block(item: syn::ItemFn) -> (String, CustomBlock) {
+        let block = recreate_netsblox_extension_block(&item, &item.attrs[1]);
+        (block.name.to_string(), block)
+    }
+
+    #[test]
+    fn defaults_are_split_at_top_level() {
+        assert_eq!(parse_defaults("[]"), Vec::<String>::new());
+        assert_eq!(parse_defaults("['a, b', \"c\", [1, 2], 3]"), ["a, b", "c", "[1, 2]", "3"]);
+        assert_eq!(parse_defaults("[null, {x: 1, y: 2}]"), ["null", "{x: 1, y: 2}"]);
+    }
+
+    #[test]
+    fn defaults_unescape_quotes() {
+        assert_eq!(parse_defaults(r#"['it\'s', "say \"hi\"", 'a\\b']"#), ["it's", "say \"hi\"", "a\\b"]);
+        assert_eq!(parse_defaults(r"['\',', 'x']"), ["',", "x"]);
+    }
+
+    #[test]
+    fn markdown_lists_blocks_and_slots() {
+        let info = ExtensionInfo { name: "Test" };
+        let label_parts = [("sep", LabelPart { spec: "sep", text: None, numeric: false, menu: Some(SEPARATORS), readonly: true })];
+        let blocks = [block(syn::parse_quote! {
+            /// Joins two texts.
+            #[netsblox_extension_block(name = "joinWith", category = "operators", spec = "join %s %s with %sep", defaults = "['a|b', 'it\'s', '|']")]
+            pub fn join_with(a: &str, b: &str, sep: &str) -> String { todo!() }
+        })];
+        let manifest = ExtensionManifest::new(&info, &blocks, &label_parts, &[], &[], &[]);
+
+        assert_eq!(generate_markdown(&manifest), "\
+# Test
+
+## operators
+
+### joinWith
+
+`join %s %s with %sep`
+
+Joins two texts.
+
+- Type: reporter
+- Available for: sprites and stage
+- Returns: `String`
+
+| Slot | Type | Default | Options |
+| --- | --- | --- | --- |
+| %s | text | a\\|b |  |
+| %s | text | it's |  |
+| %sep | text (read-only) | \\| | pipe (\\|), space > tab (\t) |
+
+");
+    }
+
+    #[test]
+    fn html_escapes_text() {
+        let info = ExtensionInfo { name: "<Test>" };
+        let blocks = [block(syn::parse_quote! {
+            /// Is a < b?
+            #[netsblox_extension_block(name = "less", category = "operators", spec = "%n < %n", defaults = "[1, 2]")]
+            pub fn less(a: f64, b: f64) -> bool { a < b }
+        })];
+        let manifest = ExtensionManifest::new(&info, &blocks, &[], &[], &[], &[]);
+        let html = generate_html(&manifest);
+
+        assert!(html.contains("<title>&lt;Test&gt;</title>"));
+        assert!(html.contains("<p><code>%n &lt; %n</code></p>\n<p>Is a &lt; b?</p>"));
+        assert!(html.contains("<li>Type: predicate</li>"));
+        assert!(html.contains("<tr><td>%n</td><td>number</td><td>2</td><td></td></tr>"));
+    }
+}
@@ -0,0 +1,347 @@
+use std::{any::{type_name, Any}, cell::RefCell, collections::HashMap, fmt, marker::PhantomData, rc::{Rc, Weak}};
+
+use js_sys::{Function, Object, Reflect};
+use wasm_bindgen::{convert::{FromWasmAbi, IntoWasmAbi}, describe::WasmDescribe, prelude::*};
+
+struct HandleEntry {
+    type_name: &'static str,
+    value: Rc<dyn Any>,
+    // Shared by the Rust copies of the handle, dead once all of them are dropped
+    owner: Weak<HandleOwner>,
+    // JS objects created for the handle that have not been garbage collected yet
+    wrappers: u32,
+}
+
+// Every Rust copy of a handle holds the same owner, which releases the value once the last copy is dropped,
+// unless JS still references the handle
+struct HandleOwner {
+    id: u32,
+}
+
+impl Drop for HandleOwner {
+    fn drop(&mut self) {
+        // The store may already be gone when values holding handles are dropped at thread exit
+        let entry = HANDLES.try_with(|store| {
+            let mut store = store.borrow_mut();
+            let entry = store.entries.get(&self.id)?;
+            if entry.wrappers == 0 { store.entries.remove(&self.id) } else { None }
+        });
+        drop(entry); // Dropped outside the borrow in case the value holds handles itself
+    }
+}
+
+#[derive(Default)]
+struct HandleStore {
+    next_id: u32,
+    entries: HashMap<u32, HandleEntry>,
+    // Shared prototype giving handles a readable toString in watchers
+    prototype: Option<Object>,
+    // FinalizationRegistry releasing values once NetsBlox no longer references their handle
+    registry: Option<JsValue>,
+}
+
+thread_local! {
+    static HANDLES: RefCell<HandleStore> = RefCell::new(HandleStore::default());
+}
+
+/// Error returned when a value passed to a block is not a usable handle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandleError {
+    /// The value is not a handle at all
+    NotAHandle,
+    /// The handle was released, e.g. because a new project was opened
+    Released { id: u32 },
+    /// The handle refers to a value of another type
+    WrongType { expected: &'static str, found: &'static str },
+}
+
+impl fmt::Display for HandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandleError::NotAHandle => write!(f, "expected a handle"),
+            HandleError::Released { id } => write!(f, "handle #{id} no longer exists"),
+            HandleError::WrongType { expected, found } => write!(f, "expected a {expected} handle, got a {found} handle"),
+        }
+    }
+}
+
+impl std::error::Error for HandleError {}
+
+impl From<HandleError> for JsValue {
+    fn from(e: HandleError) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+/// An opaque reference to a Rust value that NetsBlox scripts can store in variables and lists.
+///
+/// Reporters return a `Handle<T>` to give a value to NetsBlox, and blocks taking `Handle<T>` parameters get it back,
+/// with an error if the student passes something else. Values are dropped once neither NetsBlox nor Rust holds the handle,
+/// or when a new role is opened.
+pub struct Handle<T> {
+    id: u32,
+    owner: Rc<HandleOwner>,
+    _type: PhantomData<T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self { id: self.id, owner: self.owner.clone(), _type: PhantomData }
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle<{}>(#{})", short_type_name::<T>(), self.id)
+    }
+}
+
+// Type names are shown to students, so module paths are dropped
+pub(crate) fn short_type_name<T>() -> &'static str {
+    let name = type_name::<T>();
+    let base = name.split('<').next().unwrap_or(name);
+    &name[base.rfind("::").map(|i| i + 2).unwrap_or(0)..]
+}
+
+impl<T: 'static> Handle<T> {
+    /// Store a value and create a handle to it
+    pub fn new(value: T) -> Self {
+        HANDLES.with_borrow_mut(|store| {
+            store.next_id += 1;
+            let id = store.next_id;
+            let owner = Rc::new(HandleOwner { id });
+            store.entries.insert(id, HandleEntry { type_name: short_type_name::<T>(), value: Rc::new(RefCell::new(value)), owner: Rc::downgrade(&owner), wrappers: 0 });
+            Self { id, owner, _type: PhantomData }
+        })
+    }
+
+    // Recover a handle by id, sharing the owner of any Rust copies still alive
+    fn from_id(id: u32) -> Result<Self, HandleError> {
+        let owner = HANDLES.with_borrow_mut(|store| {
+            let entry = store.entries.get_mut(&id).ok_or(HandleError::Released { id })?;
+            if !(*entry.value).is::<RefCell<T>>() {
+                return Err(HandleError::WrongType { expected: short_type_name::<T>(), found: entry.type_name });
+            }
+            Ok(entry.owner.upgrade().unwrap_or_else(|| {
+                let owner = Rc::new(HandleOwner { id });
+                entry.owner = Rc::downgrade(&owner);
+                owner
+            }))
+        })?;
+        Ok(Self { id, owner, _type: PhantomData })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn cell(&self) -> Result<Rc<RefCell<T>>, HandleError> {
+        HANDLES.with_borrow(|store| {
+            let entry = store.entries.get(&self.id).ok_or(HandleError::Released { id: self.id })?;
+            entry.value.clone().downcast::<RefCell<T>>().map_err(|_| HandleError::WrongType { expected: short_type_name::<T>(), found: entry.type_name })
+        })
+    }
+
+    /// Access the value behind the handle
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> Result<R, HandleError> {
+        Ok(f(&self.cell()?.borrow()))
+    }
+
+    /// Mutably access the value behind the handle
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, HandleError> {
+        Ok(f(&mut self.cell()?.borrow_mut()))
+    }
+
+    /// Drop the value now, making every copy of the handle invalid
+    pub fn release(self) {
+        release_handle(self.id);
+    }
+
+    /// Recover a handle from a value passed in by NetsBlox
+    pub fn from_js(value: &JsValue) -> Result<Self, HandleError> {
+        let id = Reflect::get(value, &"rustHandle".into()).ok().and_then(|x| x.as_f64()).ok_or(HandleError::NotAHandle)? as u32;
+        Self::from_id(id)
+    }
+
+    /// Create a JS object NetsBlox can store for this handle.
+    ///
+    /// Every call creates a new object, and the value is kept until all of them are garbage collected and no Rust copy is left.
+    pub fn to_js(&self) -> JsValue {
+        HANDLES.with_borrow_mut(|store| {
+            let prototype = store.prototype.get_or_insert_with(|| {
+                let prototype = Object::new();
+                let to_string = Function::new_no_args("return '<' + this.rustType + ' #' + this.rustHandle + '>';");
+                Reflect::set(&prototype, &"toString".into(), &to_string).unwrap();
+                prototype
+            });
+
+            let obj = Object::create(prototype);
+            Reflect::set(&obj, &"rustHandle".into(), &self.id.into()).unwrap();
+            Reflect::set(&obj, &"rustType".into(), &short_type_name::<T>().into()).unwrap();
+
+            let registry = store.registry.get_or_insert_with(|| finalization_registry(finalize_handle));
+            register_finalizer(registry, &obj, self.id);
+            add_wrapper(store, self.id);
+
+            obj.into()
+        })
+    }
+}
+
+// Create a JS FinalizationRegistry calling `release` with the id objects were registered with
+pub(crate) fn finalization_registry(release: fn(u32)) -> JsValue {
+    let constructor = Reflect::get(&js_sys::global(), &"FinalizationRegistry".into()).ok().and_then(|x| x.dyn_into::<Function>().ok());
+    match constructor {
+        Some(constructor) => {
+            let cleanup = Closure::<dyn Fn(JsValue)>::new(move |id: JsValue| {
+                if let Some(id) = id.as_f64() {
+                    release(id as u32);
+                }
+            });
+            let registry = Reflect::construct(&constructor, &js_sys::Array::of1(cleanup.as_ref())).unwrap_or(JsValue::UNDEFINED);
+            cleanup.forget();
+            registry
+        }
+        None => JsValue::UNDEFINED, // Values then live until the next role is opened
+    }
+}
+
+// Register an object to be released once JS no longer references it
+pub(crate) fn register_finalizer(registry: &JsValue, obj: &JsValue, id: u32) {
+    if let Ok(register) = Reflect::get(registry, &"register".into()).and_then(|x| x.dyn_into::<Function>()) {
+        let _ = register.call2(registry, obj, &id.into());
+    }
+}
+
+fn add_wrapper(store: &mut HandleStore, id: u32) {
+    if let Some(entry) = store.entries.get_mut(&id) {
+        entry.wrappers += 1;
+    }
+}
+
+// Called when a JS object for the handle is garbage collected, releasing the value once neither JS nor Rust holds it
+fn finalize_handle(id: u32) {
+    let entry = HANDLES.with_borrow_mut(|store| {
+        let entry = store.entries.get_mut(&id)?;
+        entry.wrappers = entry.wrappers.saturating_sub(1);
+        if entry.wrappers == 0 && entry.owner.strong_count() == 0 { store.entries.remove(&id) } else { None }
+    });
+    drop(entry); // Dropped outside the borrow in case the value holds handles itself
+}
+
+fn release_handle(id: u32) {
+    let entry = HANDLES.with_borrow_mut(|store| store.entries.remove(&id));
+    drop(entry); // Dropped outside the borrow in case the value holds handles itself
+}
+
+/// Drop every value referenced by a handle
+pub fn clear_handles() {
+    let entries = HANDLES.with_borrow_mut(|store| std::mem::take(&mut store.entries));
+    drop(entries);
+}
+
+impl<T: 'static> From<Handle<T>> for JsValue {
+    fn from(handle: Handle<T>) -> Self {
+        handle.to_js()
+    }
+}
+
+impl<T: 'static> TryFrom<&JsValue> for Handle<T> {
+    type Error = HandleError;
+
+    fn try_from(value: &JsValue) -> Result<Self, Self::Error> {
+        Handle::from_js(value)
+    }
+}
+
+// Handles cross into JS as plain values, so they can be used directly as block parameters and return values
+impl<T> WasmDescribe for Handle<T> {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl<T: 'static> IntoWasmAbi for Handle<T> {
+    type Abi = <JsValue as IntoWasmAbi>::Abi;
+
+    fn into_abi(self) -> Self::Abi {
+        self.to_js().into_abi()
+    }
+}
+
+impl<T: 'static> FromWasmAbi for Handle<T> {
+    type Abi = <JsValue as FromWasmAbi>::Abi;
+
+    unsafe fn from_abi(js: Self::Abi) -> Self {
+        Handle::from_js(&JsValue::from_abi(js)).unwrap_or_else(|e| wasm_bindgen::throw_str(&e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exists(id: u32) -> bool {
+        HANDLES.with_borrow(|store| store.entries.contains_key(&id))
+    }
+
+    #[test]
+    fn values_live_until_every_wrapper_is_collected() {
+        let handle = Handle::new(vec![1, 2]);
+        let id = handle.id();
+        HANDLES.with_borrow_mut(|store| {
+            add_wrapper(store, id);
+            add_wrapper(store, id);
+        });
+        drop(handle);
+
+        finalize_handle(id);
+        assert_eq!(Handle::<Vec<i32>>::from_id(id).unwrap().with(|x| x.len()), Ok(2));
+
+        finalize_handle(id);
+        assert!(!exists(id));
+        assert_eq!(Handle::<Vec<i32>>::from_id(id).unwrap_err(), HandleError::Released { id });
+    }
+
+    #[test]
+    fn rust_copies_keep_values_alive() {
+        let handle = Handle::new(String::from("kept"));
+        let id = handle.id();
+        HANDLES.with_borrow_mut(|store| add_wrapper(store, id));
+
+        // The student's variable was reassigned while a block still holds the handle
+        let held = handle.clone();
+        drop(handle);
+        finalize_handle(id);
+        assert_eq!(held.with(|x| x.clone()), Ok("kept".to_string()));
+
+        let recovered = Handle::<String>::from_id(id).unwrap();
+        drop(held);
+        assert!(exists(id));
+        drop(recovered);
+        assert!(!exists(id));
+    }
+
+    #[test]
+    fn handles_never_given_to_js_are_dropped_with_rust_copies() {
+        let inner = Handle::new(1.5);
+        let inner_id = inner.id();
+        let outer = Handle::new(vec![inner]);
+        let outer_id = outer.id();
+
+        drop(outer);
+        assert!(!exists(outer_id));
+        assert!(!exists(inner_id));
+    }
+
+    #[test]
+    fn handles_check_types() {
+        let handle = Handle::new(1.5);
+        assert_eq!(Handle::<String>::from_id(handle.id()).unwrap_err(), HandleError::WrongType { expected: "String", found: "f64" });
+
+        handle.with_mut(|x| *x *= 2.0).unwrap();
+        assert_eq!(handle.clone().with(|x| *x), Ok(3.0));
+        handle.clone().release();
+        assert_eq!(handle.with(|x| *x), Err(HandleError::Released { id: handle.id() }));
+    }
+}
@@ -0,0 +1,67 @@
+    // Helpers that need nothing from NetsBlox but its List class, kept in their own file so they can be tested on their own
+
+    // Add saved state to the end of the stage's XML. Sliced rather than replaced, since replacement strings expand `$&` and the like
+    function $NO_SPACE_EXTENSION_NAME_insertState(xml, stateXml) {
+        const end = '</stage>';
+        if (!xml.endsWith(end)) {
+            return xml;
+        }
+        return xml.slice(0, -end.length) + stateXml + end;
+    }
+
+    // Byte and number vectors are passed as typed arrays, which wasm-bindgen copies in one go instead of item by item
+    function $NO_SPACE_EXTENSION_NAME_arrayItems(value, expected) {
+        if (value === null || value === undefined || value === '') {
+            return [];
+        }
+        const items = value instanceof List ? value.itemsArray() : value;
+        if (!Array.isArray(items) && !ArrayBuffer.isView(items)) {
+            throw new Error(`expected ${expected}`);
+        }
+        return items;
+    }
+
+    function $NO_SPACE_EXTENSION_NAME_toBytes(value) {
+        if (value instanceof Uint8Array) {
+            return value;
+        }
+        if (typeof value === 'string' && value !== '') {
+            // Text is base64, optionally as a data URL
+            try {
+                const binary = atob(value.replace(/^data:[^,]*;base64,/, '').trim());
+                return Uint8Array.from(binary, c => c.charCodeAt(0));
+            } catch {
+                throw new Error('expected a list of bytes or base64 text');
+            }
+        }
+        const items = $NO_SPACE_EXTENSION_NAME_arrayItems(value, 'a list of bytes or base64 text');
+        const bytes = new Uint8Array(items.length);
+        for (let i = 0; i < items.length; i++) {
+            const x = Number(items[i]);
+            // Number() reads blank text as 0, so blank items are rejected explicitly
+            if (!Number.isInteger(x) || x < 0 || x > 255 || typeof items[i] === 'boolean' || String(items[i]).trim() === '') {
+                throw new Error(`item ${i + 1}: expected a byte from 0 to 255, got ${items[i]}`);
+            }
+            bytes[i] = x;
+        }
+        return bytes;
+    }
+
+    function $NO_SPACE_EXTENSION_NAME_toNumbers(value) {
+        if (value instanceof Float64Array) {
+            return value;
+        }
+        const items = $NO_SPACE_EXTENSION_NAME_arrayItems(value, 'a list of numbers');
+        if (ArrayBuffer.isView(items)) {
+            return Float64Array.from(items);
+        }
+        const numbers = new Float64Array(items.length);
+        for (let i = 0; i < items.length; i++) {
+            const x = Number(items[i]);
+            if (Number.isNaN(x) || typeof items[i] === 'boolean' || String(items[i]).trim() === '') {
+                throw new Error(`item ${i + 1}: expected a number, got ${items[i]}`);
+            }
+            numbers[i] = x;
+        }
+        return numbers;
+    }
@@ -0,0 +1,249 @@
+use std::fmt;
+
+use js_sys::{Reflect, Uint8Array};
+use wasm_bindgen::{convert::{FromWasmAbi, IntoWasmAbi}, describe::WasmDescribe, prelude::*};
+
+#[wasm_bindgen(inline_js = r#"
+export function costumePixels(costume) {
+    if (!(costume instanceof Costume)) {
+        throw new Error('expected a costume');
+    }
+    // Drawn onto a fresh canvas so vector costumes are rasterized too
+    const { width, height } = costume.contents;
+    const canvas = newCanvas(new Point(width, height), true);
+    const ctx = canvas.getContext('2d');
+    ctx.drawImage(costume.contents, 0, 0);
+    return { width, height, name: costume.name, data: new Uint8Array(ctx.getImageData(0, 0, width, height).data.buffer) };
+}
+
+export function makeCostume(width, height, data, name) {
+    const canvas = newCanvas(new Point(width, height), true);
+    canvas.getContext('2d').putImageData(new ImageData(new Uint8ClampedArray(data), width, height), 0, 0);
+    return new Costume(canvas, name);
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = costumePixels, catch)]
+    fn costume_pixels(costume: &JsValue) -> Result<JsValue, JsValue>;
+    #[wasm_bindgen(js_name = makeCostume)]
+    fn make_costume(width: u32, height: u32, data: &[u8], name: &str) -> JsValue;
+}
+
+/// Error returned when an image cannot be created or converted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    /// The value passed to a block is not a costume
+    NotACostume,
+    /// The pixel data does not match the dimensions, or the image is empty
+    InvalidSize { width: u32, height: u32, len: usize },
+    /// PNG data could not be decoded or encoded
+    Png(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::NotACostume => write!(f, "expected a costume"),
+            ImageError::InvalidSize { width, height, len } => write!(f, "{len} bytes of pixel data do not make a {width}x{height} image"),
+            ImageError::Png(e) => write!(f, "invalid PNG: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<ImageError> for JsValue {
+    fn from(e: ImageError) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+/// An RGBA image, converted from a costume passed to a block or into a costume when returned from one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    name: String,
+}
+
+impl Image {
+    /// Create an image from RGBA pixel data, 4 bytes per pixel in rows from the top
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Result<Self, ImageError> {
+        if width == 0 || height == 0 || data.len() != width as usize * height as usize * 4 {
+            return Err(ImageError::InvalidSize { width, height, len: data.len() });
+        }
+        Ok(Self { width, height, data, name: "costume".into() })
+    }
+
+    /// Create a transparent image
+    pub fn blank(width: u32, height: u32) -> Result<Self, ImageError> {
+        Self::new(width, height, vec![0; width as usize * height as usize * 4])
+    }
+
+    pub fn from_png(bytes: &[u8]) -> Result<Self, ImageError> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8() | png::Transformations::ALPHA);
+        let mut reader = decoder.read_info().map_err(|e| ImageError::Png(e.to_string()))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(|e| ImageError::Png(e.to_string()))?;
+        buf.truncate(info.buffer_size());
+
+        // The alpha transformation leaves grayscale images with two bytes per pixel
+        let data = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|x| [x[0], x[0], x[0], x[1]]).collect(),
+            x => return Err(ImageError::Png(format!("unsupported color type {x:?}"))),
+        };
+        Self::new(info.width, info.height, data)
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, ImageError> {
+        let mut res = vec![];
+        let mut encoder = png::Encoder::new(&mut res, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| ImageError::Png(e.to_string()))?;
+        writer.write_image_data(&self.data).map_err(|e| ImageError::Png(e.to_string()))?;
+        writer.finish().map_err(|e| ImageError::Png(e.to_string()))?;
+        Ok(res)
+    }
+
+    /// Set the name of the costume created from this image
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// RGBA pixel data, 4 bytes per pixel in rows from the top
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    fn offset(&self, x: u32, y: u32) -> Option<usize> {
+        (x < self.width && y < self.height).then(|| (y as usize * self.width as usize + x as usize) * 4)
+    }
+
+    /// The RGBA value of a pixel, or `None` if it is outside the image
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        let i = self.offset(x, y)?;
+        Some([self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3]])
+    }
+
+    /// Set a pixel, ignoring pixels outside the image
+    pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        if let Some(i) = self.offset(x, y) {
+            self.data[i..i + 4].copy_from_slice(&rgba);
+        }
+    }
+
+    /// Read the pixels of a costume passed in by NetsBlox
+    pub fn from_js(costume: &JsValue) -> Result<Self, ImageError> {
+        let res = costume_pixels(costume).map_err(|_| ImageError::NotACostume)?;
+        let get = |key: &str| Reflect::get(&res, &key.into()).map_err(|_| ImageError::NotACostume);
+        let width = get("width")?.as_f64().unwrap_or_default() as u32;
+        let height = get("height")?.as_f64().unwrap_or_default() as u32;
+        let data = Uint8Array::new(&get("data")?).to_vec();
+        let name = get("name")?.as_string().unwrap_or_default();
+        Ok(Self::new(width, height, data)?.with_name(name))
+    }
+
+    /// Create the costume NetsBlox stores for this image
+    pub fn to_js(&self) -> JsValue {
+        make_costume(self.width, self.height, &self.data, &self.name)
+    }
+}
+
+impl From<Image> for JsValue {
+    fn from(image: Image) -> Self {
+        image.to_js()
+    }
+}
+
+impl TryFrom<&JsValue> for Image {
+    type Error = ImageError;
+
+    fn try_from(value: &JsValue) -> Result<Self, Self::Error> {
+        Image::from_js(value)
+    }
+}
+
+// Images cross into JS as costumes, so they can be used directly as block parameters and return values
+impl WasmDescribe for Image {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl IntoWasmAbi for Image {
+    type Abi = <JsValue as IntoWasmAbi>::Abi;
+
+    fn into_abi(self) -> Self::Abi {
+        self.to_js().into_abi()
+    }
+}
+
+impl FromWasmAbi for Image {
+    type Abi = <JsValue as FromWasmAbi>::Abi;
+
+    unsafe fn from_abi(js: Self::Abi) -> Self {
+        Image::from_js(&JsValue::from_abi(js)).unwrap_or_else(|e| wasm_bindgen::throw_str(&e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip() {
+        let mut image = Image::blank(3, 2).unwrap();
+        image.set_pixel(0, 0, [255, 0, 0, 255]);
+        image.set_pixel(2, 1, [10, 20, 30, 40]);
+
+        let decoded = Image::from_png(&image.to_png().unwrap()).unwrap();
+        assert_eq!(decoded, image);
+        assert_eq!(decoded.pixel(2, 1), Some([10, 20, 30, 40]));
+        assert_eq!(decoded.pixel(3, 0), None);
+    }
+
+    #[test]
+    fn grayscale_pngs_are_expanded() {
+        let mut bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 200]).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(Image::from_png(&bytes).unwrap().into_data(), [0, 0, 0, 255, 200, 200, 200, 255]);
+    }
+
+    #[test]
+    fn invalid_images_are_rejected() {
+        assert_eq!(Image::new(2, 2, vec![0; 15]), Err(ImageError::InvalidSize { width: 2, height: 2, len: 15 }));
+        assert_eq!(Image::blank(0, 5), Err(ImageError::InvalidSize { width: 0, height: 5, len: 0 }));
+        assert!(matches!(Image::from_png(b"not a png"), Err(ImageError::Png(_))));
+    }
+}
@@ -1,10 +1,59 @@
 use proc_macro2::TokenTree;
 use serde::Serialize;
-use std::{fs::File, error::Error, io::{Read, Write}, vec, collections::{HashMap, HashSet}, path::Path, fmt::Write as FmtWrite};
+use std::{cell::RefCell, fs::File, error::Error, io::{Read, Write}, vec, collections::{HashMap, HashSet}, path::{Path, PathBuf}, fmt::Write as FmtWrite};
 use regex::Regex;
+use quote::ToTokens;
 use simple_error::bail;
 use syn::{Attribute, Expr, ExprCall, ExprPath, ExprLit, ExprReference, ExprArray, ExprStruct, Lit, Item, ItemConst, ItemFn, Member, Meta, PathSegment};
 use std::collections::BTreeSet;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+// Runtime types built by the testing module answer from their fake instead of NetsBlox
+macro_rules! mocked {
+    ($obj:expr, $mock:ident => $e:expr) => {
+        #[cfg(feature = "testing")]
+        if let Some($mock) = &$obj.mock {
+            return $e;
+        }
+    };
+}
+
+mod manifest;
+pub use manifest::*;
+mod docs;
+pub use docs::*;
+mod locale;
+pub use locale::*;
+mod handle;
+pub use handle::*;
+mod ring;
+pub use ring::*;
+mod upvar;
+pub use upvar::*;
+mod messaging;
+pub use messaging::*;
+mod rpc;
+pub use rpc::*;
+mod sprite;
+pub use sprite::*;
+mod process;
+pub use process::*;
+mod cancel;
+pub use cancel::*;
+mod state;
+pub use state::*;
+mod persist;
+pub use persist::*;
+mod value;
+pub use value::*;
+mod image;
+pub use image::*;
+mod audio;
+pub use audio::*;
+mod pen;
+pub use pen::*;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 macro_rules! count_exprs {
     () => { 0usize };
@@ -44,10 +93,39 @@ pub struct CustomBlock {
     pub spec: &'static str,
     pub defaults: &'static str,
     pub impl_fn: &'static str,
+    /// JavaScript function used instead of a Rust function, for blocks declared with `netsblox_extension_js_block`
+    pub js_impl: Option<&'static str>,
     pub target: TargetObject,
     pub pass_proc: bool,
     pub pad_top: bool,
     pub pad_bottom: bool,
+    /// Doc comments on the block's item
+    pub doc: Option<&'static str>,
+    /// Type returned by the Rust function, with any Result unwrapped
+    pub return_type: Option<&'static str>,
+    /// Text shown by the block's help menu item, from the `help` attribute or the doc comments
+    pub help: Option<&'static str>,
+    /// Whether the Rust function is async, in which case the calling process waits for it without blocking the IDE
+    pub is_async: bool,
+    /// Whether the block runs on a Web Worker, falling back to the main thread where workers are unavailable
+    pub worker: bool,
+    /// Whether the Rust function takes a `CancellationToken`, which comes first after the process
+    pub pass_token: bool,
+    /// Time after which the block is cancelled and reports an error
+    pub timeout_ms: Option<u32>,
+    /// Array types of the Rust function's slot parameters in order, with `None` for other parameters
+    pub array_params: Vec<Option<ArrayType>>,
+    /// Array type returned by the Rust function, if any
+    pub array_return: Option<ArrayType>,
+}
+
+/// Parameters and return values of these types are passed as typed arrays, which are copied in one go
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum ArrayType {
+    /// `Vec<u8>` or `&[u8]`, passed as a `Uint8Array`
+    Bytes,
+    /// `Vec<f64>` or `&[f64]`, passed as a `Float64Array`
+    Numbers,
 }
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
@@ -106,18 +184,142 @@ pub struct ExtensionSetting {
     pub hidden: bool
 }
 
+thread_local! {
+    // Settings where localStorage is unavailable, e.g. on workers and in native tests
+    static SETTINGS: RefCell<HashMap<&'static str, bool>> = RefCell::new(HashMap::new());
+}
+
+fn local_storage() -> Option<web_sys::Storage> {
+    if !cfg!(target_arch = "wasm32") {
+        return None;
+    }
+    web_sys::window()?.local_storage().ok()?
+}
+
 impl ExtensionSetting {
     pub fn get(&self) -> bool {
-        let window = &web_sys::window().unwrap();
-        let storage = window.local_storage().unwrap().unwrap();
-        storage.get_item(self.id).unwrap().unwrap_or(if self.default_value { "true".to_string() } else { "false".to_string()} ) == "true"
+        match local_storage() {
+            Some(storage) => storage.get_item(self.id).ok().flatten().map_or(self.default_value, |x| x == "true"),
+            None => SETTINGS.with_borrow(|settings| settings.get(self.id).copied().unwrap_or(self.default_value)),
+        }
     }
 
     pub fn set(&self, val: bool) {
-        let window = web_sys::window();
-        let local_storage = window.unwrap().local_storage().unwrap().unwrap();
-        local_storage.set_item(self.id, if val {"true"} else {"false"}).unwrap();
+        match local_storage() {
+            Some(storage) => storage.set_item(self.id, if val {"true"} else {"false"}).unwrap(),
+            None => {
+                SETTINGS.with_borrow_mut(|settings| settings.insert(self.id, val));
+            }
+        }
+    }
+}
+
+/// Slot types that are passed to Rust as a `Ring`
+const RING_LABEL_PARTS: &[&str] = &["repRing", "cmdRing", "predRing"];
+/// C-slot types, whose enclosed script is passed to Rust as a `Ring`
+const SCRIPT_LABEL_PARTS: &[&str] = &["c", "cs", "ca"];
+/// Slot types that are passed to Rust as an `Upvar`
+const UPVAR_LABEL_PARTS: &[&str] = &["upvar"];
+
+/// Placeholders every extension template must contain, since the build always fills them
+const REQUIRED_PLACEHOLDERS: &[&str] = &["$EXTENSION_NAME", "$NO_SPACE_EXTENSION_NAME", "$SETTINGS", "$MENU", "$CATEGORIES", "$PALETTE", "$BLOCKS", "$LABELPARTS", "$IMPORTS_LIST", "$WINDOW_IMPORTS", "$PACKAGE_NAME", "$HELPERS"];
+
+/// Named points in the extension template where user JavaScript can be injected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectionPoint {
+    /// End of the Extension subclass constructor, after `super` is called
+    Constructor,
+    /// Body of the `onOpenRole` method
+    OnOpenRole,
+    /// Extra methods added to the Extension subclass
+    Methods,
+    /// Module script, after the wasm module is initialized and its functions are made available
+    AfterInit,
+}
+
+impl InjectionPoint {
+    fn placeholder(&self) -> &'static str {
+        match self {
+            InjectionPoint::Constructor => "$INJECT_CONSTRUCTOR",
+            InjectionPoint::OnOpenRole => "$INJECT_ON_OPEN_ROLE",
+            InjectionPoint::Methods => "$INJECT_METHODS",
+            InjectionPoint::AfterInit => "$INJECT_AFTER_INIT",
+        }
+    }
+}
+
+/// JavaScript provided either inline or as a path relative to the crate root
+#[derive(Debug, Clone)]
+pub enum JsSource {
+    Inline(String),
+    File(PathBuf),
+}
+
+impl JsSource {
+    fn read(&self) -> Result<String, Box<dyn Error>> {
+        match self {
+            JsSource::Inline(code) => Ok(code.clone()),
+            JsSource::File(path) => {
+                let mut code = String::new();
+                File::open(path).map_err(|e| format!("failed to open JS file {}: {e}", path.display()))?.read_to_string(&mut code)?;
+                Ok(code)
+            }
+        }
+    }
+}
+
+/// Options for customizing the generated extension file
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    /// Template to use instead of the bundled one, relative to the crate root
+    pub template: Option<PathBuf>,
+    /// Snippets to place at injection points, in order
+    pub injections: Vec<(InjectionPoint, JsSource)>,
+    /// Formats to generate block documentation in, grouped by category
+    pub docs: Vec<DocFormat>,
+}
+
+// Snippets placed inside the module script end up in a JS template literal
+fn escape_template_literal(code: &str) -> String {
+    code.replace('\\', "\\\\").replace('`', "\\`").replace("${", "\\${")
+}
+
+// Fill a placeholder for an optional feature, only failing if the template lacks it and the feature is in use
+fn replace_feature_placeholder(content: &mut String, placeholder: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    if !value.is_empty() && !content.contains(placeholder) {
+        bail!("Template is missing placeholder {placeholder}, which is needed by this extension");
+    }
+
+    *content = content.replace(placeholder, value);
+    Ok(())
+}
+
+// Fill injection points, failing if a snippet targets a placeholder the template lacks
+fn apply_injections(content: &mut String, injections: &[(InjectionPoint, JsSource)]) -> Result<(), Box<dyn Error>> {
+    for point in [InjectionPoint::Constructor, InjectionPoint::OnOpenRole, InjectionPoint::Methods, InjectionPoint::AfterInit] {
+        let mut code = String::new();
+        for (_, source) in injections.iter().filter(|(p, _)| *p == point) {
+            let snippet = source.read()?;
+            code += if point == InjectionPoint::AfterInit { escape_template_literal(&snippet) } else { snippet }.as_str();
+            code.push('\n');
+        }
+
+        if !code.is_empty() && !content.contains(point.placeholder()) {
+            bail!("Template is missing placeholder {} required by injected {point:?} code", point.placeholder());
+        }
+
+        *content = content.replace(point.placeholder(), &code);
     }
+
+    Ok(())
+}
+
+/// Called by the generated extension when a role is opened, to drop state from the previous project
+#[wasm_bindgen]
+pub fn netsblox_extension_open_role() {
+    clear_handles();
+    clear_rust_rings();
+    clear_sprite_state();
 }
 
 // Macro to allow build script to print output
@@ -198,16 +400,53 @@ fn recreate_netsblox_extension_custom_category(item: &ItemConst) -> CustomCatego
 
 // Turn syn item into instance
 fn recreate_netsblox_extension_block(item: &ItemFn, attr: &Attribute) -> CustomBlock {
+    let impl_fn = Box::leak(item.sig.ident.to_string().into_boxed_str()); // Get information from function signature
+    let mut block = recreate_custom_block(attr, &item.attrs, impl_fn, None, Some(&item.sig.output));
+    block.is_async = item.sig.asyncness.is_some();
+
+    // Cancellation tokens are recognized by type, so blocks don't need another attribute to receive one
+    let token_index = item.sig.inputs.iter().position(|arg| match arg {
+        syn::FnArg::Typed(t) => matches!(t.ty.as_ref(), syn::Type::Path(p) if p.path.segments.last().is_some_and(|x| x.ident == "CancellationToken")),
+        syn::FnArg::Receiver(_) => false,
+    });
+    if let Some(i) = token_index {
+        if !block.is_async || block.worker {
+            panic!("Block {} takes a CancellationToken, so it must be an async fn not run on a worker", block.name);
+        }
+        if i != block.pass_proc as usize {
+            panic!("The CancellationToken of block {} must be its first parameter after the process", block.name);
+        }
+        block.pass_token = true;
+    }
+    block.array_params = item.sig.inputs.iter().skip(block.pass_proc as usize + block.pass_token as usize).map(|arg| match arg {
+        syn::FnArg::Typed(t) => extract_array_type(&t.ty),
+        syn::FnArg::Receiver(_) => None,
+    }).collect();
+    if block.timeout_ms.is_some() && !block.is_async && !block.worker {
+        panic!("Block {} has a timeout, so it must be an async fn or run on a worker", block.name);
+    }
+    block
+}
+
+// Turn syn item into instance, JS blocks use the const value as the block function
+fn recreate_netsblox_extension_js_block(item: &ItemConst, attr: &Attribute) -> CustomBlock {
+    let impl_fn = Box::leak(item.ident.to_string().into_boxed_str());
+    recreate_custom_block(attr, &item.attrs, impl_fn, Some(extract_js_source(&item.expr)), None)
+}
+
+fn recreate_custom_block(attr: &Attribute, item_attrs: &[Attribute], impl_fn: &'static str, js_impl: Option<&'static str>, output: Option<&syn::ReturnType>) -> CustomBlock {
     let mut name: Option<&'static str> = None;
     let mut category: Option<&'static str> = None;
     let mut spec: Option<&'static str> = None;
     let mut defaults: Option<&'static str> = None;
     let mut target: Option<TargetObject> = None;
     let mut pass_proc: Option<bool> = None;
-    let mut impl_fn: Option<&'static str> = None;
     let mut block_type: Option<BlockType> = None;
     let mut pad_top: Option<bool> = None;
     let mut pad_bottom: Option<bool> = None;
+    let mut help: Option<Option<&'static str>> = None;
+    let mut worker: Option<bool> = None;
+    let mut timeout_ms: Option<Option<u32>> = None;
 
     // Parse information stored in attribute
     if let Meta::List(l) = &attr.meta {
@@ -225,6 +464,9 @@ fn recreate_netsblox_extension_block(item: &ItemFn, attr: &Attribute) -> CustomB
                     "target" => target = Some(extract_target_object_meta(value)),
                     "pad_top" => pad_top = Some(extract_bool_meta(value)),
                     "pad_bottom" => pad_bottom = Some(extract_bool_meta(value)),
+                    "help" => help = Some(Some(extract_string_value_meta(value))),
+                    "worker" => worker = Some(extract_bool_meta(value)),
+                    "timeout_ms" => timeout_ms = Some(Some(extract_u32_meta(value))),
                     x => panic!("unknown extension block attr field: {x:?}"),
                 }
                 x => panic!("unknown meta attr format: {x:?}"),
@@ -237,14 +479,30 @@ fn recreate_netsblox_extension_block(item: &ItemFn, attr: &Attribute) -> CustomB
     if defaults.is_none() { defaults = Some("[]"); }
     if target.is_none() { target = Some(TargetObject::Both); }
     if pass_proc.is_none() { pass_proc = Some(false); }
+    if worker.is_none() { worker = Some(false); }
+    if timeout_ms.is_none() { timeout_ms = Some(None); }
 
-    if impl_fn.is_none() {
-        impl_fn = Some(Box::leak(item.sig.ident.to_string().into_boxed_str())); // Get information from function signature
+    // JS blocks are called with the process as `this` and never go through the generated Rust call
+    if pass_proc == Some(true) && js_impl.is_some() {
+        panic!("JS block {} cannot use pass_proc, its code can use `this` for the process instead", name.unwrap_or(impl_fn));
     }
+
+    // Workers have their own copy of the module and no access to the IDE
+    if worker == Some(true) && pass_proc == Some(true) {
+        panic!("Block {} runs on a worker, so it cannot use pass_proc", name.unwrap_or(impl_fn));
+    }
+    if worker == Some(true) && js_impl.is_some() {
+        panic!("JS block {} cannot run on a worker", name.unwrap_or(impl_fn));
+    }
+    if timeout_ms != Some(None) && js_impl.is_some() {
+        panic!("JS block {} cannot have a timeout", name.unwrap_or(impl_fn));
+    }
+
     if block_type.is_none() {
-        block_type = Some(match &item.sig.output {
-            syn::ReturnType::Default => BlockType::Command,
-            syn::ReturnType::Type(_, b) => match b.as_ref() {
+        block_type = Some(match output {
+            None => BlockType::Command, // JS blocks have no signature to infer from
+            Some(syn::ReturnType::Default) => BlockType::Command,
+            Some(syn::ReturnType::Type(_, b)) => match b.as_ref() {
                 syn::Type::Tuple(t) if t.elems.is_empty() => BlockType::Command,
                 syn::Type::Path(p) if p.path.segments.first().unwrap().ident.to_string() == "bool" => BlockType::Predicate,
                 syn::Type::Path(p) if p.path.segments.first().unwrap().ident.to_string() == "Result" => match &p.path.segments.first().unwrap().arguments {
@@ -263,7 +521,21 @@ fn recreate_netsblox_extension_block(item: &ItemFn, attr: &Attribute) -> CustomB
         });
     }
 
-    try_construct!(CustomBlock { name, block_type, category, spec, defaults, impl_fn, target, pass_proc, pad_top, pad_bottom })
+    let impl_fn = Some(impl_fn);
+    let js_impl = Some(js_impl);
+    let doc = Some(extract_doc(item_attrs));
+    let return_type = Some(output.and_then(extract_return_type));
+    if help.is_none() { help = doc; }
+
+    let is_async = Some(false);
+    let pass_token = Some(false);
+    let array_params = Some(vec![]);
+    let array_return = Some(output.and_then(|x| match x {
+        syn::ReturnType::Type(_, t) => extract_array_type(unwrap_result(t)),
+        syn::ReturnType::Default => None,
+    }));
+
+    try_construct!(CustomBlock { name, block_type, category, spec, defaults, impl_fn, js_impl, target, pass_proc, pad_top, pad_bottom, doc, return_type, help, is_async, worker, pass_token, timeout_ms, array_params, array_return })
 }
 
 // Turn syn item into instance
@@ -419,6 +691,91 @@ fn extract_string(expr: &syn::Expr) -> &'static str {
         x => panic!("unknown string expr: {x:?}"),
     }
 }
+// Join /// comments into a single string, keeping line breaks between paragraphs
+fn extract_doc(attrs: &[Attribute]) -> Option<&'static str> {
+    let lines = attrs.iter().filter_map(|attr| match &attr.meta {
+        Meta::NameValue(nv) if nv.path.is_ident("doc") => match &nv.value {
+            Expr::Lit(ExprLit { attrs: _, lit: Lit::Str(v) }) => Some(v.value()),
+            _ => None,
+        }
+        _ => None,
+    }).collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        return None;
+    }
+
+    let doc = lines.iter().map(|x| x.strip_prefix(' ').unwrap_or(x)).collect::<Vec<_>>().join("\n");
+    Some(doc.trim().to_owned().leak())
+}
+
+fn extract_return_type(output: &syn::ReturnType) -> Option<&'static str> {
+    let ty = match output {
+        syn::ReturnType::Default => return None,
+        syn::ReturnType::Type(_, b) => b.as_ref(),
+    };
+
+    // Errors are reported by the block, so only the success type is shown
+    match unwrap_result(ty) {
+        syn::Type::Tuple(t) if t.elems.is_empty() => None,
+        ty => Some(ty.to_token_stream().to_string().replace(" < ", "<").replace(" >", ">").replace(" ,", ",").replace(" :: ", "::").replace("& ", "&").leak()),
+    }
+}
+
+fn unwrap_result(ty: &syn::Type) -> &syn::Type {
+    match ty {
+        syn::Type::Path(p) if p.path.segments.last().unwrap().ident == "Result" => match &p.path.segments.last().unwrap().arguments {
+            syn::PathArguments::AngleBracketed(x) => match x.args.first() {
+                Some(syn::GenericArgument::Type(t)) => t,
+                _ => ty,
+            }
+            _ => ty,
+        }
+        _ => ty,
+    }
+}
+
+// Vectors and shared slices of bytes or numbers, mutable slices are left to wasm-bindgen
+fn extract_array_type(ty: &syn::Type) -> Option<ArrayType> {
+    let elem = match ty {
+        syn::Type::Reference(r) if r.mutability.is_none() => match r.elem.as_ref() {
+            syn::Type::Slice(s) => s.elem.as_ref(),
+            _ => return None,
+        }
+        syn::Type::Path(p) => match p.path.segments.last() {
+            Some(x) if x.ident == "Vec" => match &x.arguments {
+                syn::PathArguments::AngleBracketed(a) => match a.args.first() {
+                    Some(syn::GenericArgument::Type(t)) => t,
+                    _ => return None,
+                }
+                _ => return None,
+            }
+            _ => return None,
+        }
+        _ => return None,
+    };
+
+    match elem {
+        syn::Type::Path(p) if p.path.is_ident("u8") => Some(ArrayType::Bytes),
+        syn::Type::Path(p) if p.path.is_ident("f64") => Some(ArrayType::Numbers),
+        _ => None,
+    }
+}
+
+// JS source is either a string literal or an include_str! of a file relative to src/
+fn extract_js_source(expr: &syn::Expr) -> &'static str {
+    match expr {
+        Expr::Lit(_) => extract_string(expr).trim(),
+        Expr::Macro(m) if m.mac.path.segments.last().unwrap().ident == "include_str" => {
+            let path = m.mac.parse_body::<syn::LitStr>().expect("include_str! expects a string literal").value();
+            let mut code = String::new();
+            File::open(Path::new("./src").join(&path)).unwrap_or_else(|e| panic!("failed to open JS block file {path:?}: {e}")).read_to_string(&mut code).unwrap();
+            code.trim().to_owned().leak()
+        }
+        x => panic!("unknown JS source expr: {x:?}"),
+    }
+}
+
 fn extract_string_meta(tree: &[TokenTree]) -> &'static str {
     match tree {
         [TokenTree::Literal(lit)] => {
@@ -429,6 +786,14 @@ fn extract_string_meta(tree: &[TokenTree]) -> &'static str {
     }
 }
 
+// Like extract_string_meta, but with escapes processed, for text that is not pasted into JS as written
+fn extract_string_value_meta(tree: &[TokenTree]) -> &'static str {
+    match tree {
+        [TokenTree::Literal(lit)] => syn::parse_str::<syn::LitStr>(&lit.to_string()).unwrap_or_else(|e| panic!("expected a string literal: {e}")).value().leak(),
+        x => panic!("unknown string meta value: {x:?}"),
+    }
+}
+
 fn extract_bool(expr: &syn::Expr) -> bool {
     match expr {
         Expr::Lit(ExprLit { attrs: _, lit: Lit::Bool(v) }) => v.value,
@@ -443,6 +808,13 @@ fn extract_bool_meta(tree: &[TokenTree]) -> bool {
     }
 }
 
+fn extract_u32_meta(tree: &[TokenTree]) -> u32 {
+    match tree {
+        [TokenTree::Literal(lit)] => lit.to_string().replace('_', "").parse().unwrap_or_else(|_| panic!("unknown u32 meta value: {lit}")),
+        x => panic!("unknown u32 meta value: {x:?}"),
+    }
+}
+
 fn extract_f64(expr: &syn::Expr) -> f64 {
     match expr {
         Expr::Lit(ExprLit { attrs: _, lit: Lit::Float(v) }) => v.base10_parse().unwrap(),
@@ -450,7 +822,28 @@ fn extract_f64(expr: &syn::Expr) -> f64 {
     }
 }
 
+// Check if label parts used by block spec are known
+fn check_block_label_parts(block: &CustomBlock, label_parts_regex: &Regex, known_label_parts: &BTreeSet<&str>) {
+    let defaults = parse_defaults(block.defaults);
+    for (i, cap) in label_parts_regex.captures_iter(block.spec).enumerate() {
+        let label_part = cap.get(2).unwrap().as_str();
+        if !known_label_parts.contains(&label_part) {
+            panic!("Unknown label part %{}!", label_part);
+        }
+        if block.worker && [RING_LABEL_PARTS, SCRIPT_LABEL_PARTS, UPVAR_LABEL_PARTS].concat().contains(&label_part) {
+            panic!("Block {} runs on a worker, so it cannot have a %{} slot", block.name, label_part);
+        }
+        if UPVAR_LABEL_PARTS.contains(&label_part) && defaults.get(i).is_none_or(|x| x.is_empty()) {
+            warn!("Block {} has no default name for upvar slot {}", block.name, i + 1);
+        }
+    }
+}
+
 pub fn build() -> Result<(), Box<dyn Error>>  {
+    build_with_options(&BuildOptions::default())
+}
+
+pub fn build_with_options(options: &BuildOptions) -> Result<(), Box<dyn Error>>  {
     // Read file
     let mut file = File::open("./src/lib.rs")?;
     let mut content = String::new();
@@ -464,6 +857,9 @@ pub fn build() -> Result<(), Box<dyn Error>>  {
     let mut menu_items: Vec<(String, String)> = vec![];
     let mut settings: Vec<ExtensionSetting> = vec![];
     let mut fn_names: HashSet<String> = HashSet::new();
+    fn_names.insert("netsblox_extension_open_role".to_string());
+    fn_names.insert("netsblox_extension_save_state".to_string());
+    fn_names.insert("netsblox_extension_load_state".to_string());
 
     // Start with built-in label part specifiers
     let mut known_label_parts: BTreeSet<&str> = include_str!("builtin-types.txt").lines().map(|x| x.trim()).filter(|x| !x.is_empty()).collect();
@@ -519,6 +915,12 @@ pub fn build() -> Result<(), Box<dyn Error>>  {
                         warn!("Found setting {}", setting.name);
                         settings.push(setting);
                     },
+                    "netsblox_extension_js_block" => {
+                        let block = recreate_netsblox_extension_js_block(c, attr);
+                        warn!("Found JS block {:?}", block);
+                        check_block_label_parts(&block, &label_parts_regex, &known_label_parts);
+                        custom_blocks.push((block.name.to_string(), block));
+                    },
                     _ => {}
                 };
             }
@@ -536,14 +938,7 @@ pub fn build() -> Result<(), Box<dyn Error>>  {
                             warn!("Found custom block {:?}", block);
                             custom_blocks.push((block.name.to_string(), block.clone()));
                             fn_names.insert(block.impl_fn.to_string());
-
-                            // Check if label parts used by block spec are known
-                            for cap in label_parts_regex.captures_iter(block.spec) {
-                                let label_part = cap.get(2).unwrap().as_str();
-                                if !known_label_parts.contains(&label_part) {
-                                    panic!("Unknown label part %{}!", label_part);
-                                }
-                            }
+                            check_block_label_parts(&block, &label_parts_regex, &known_label_parts);
                         } else {
                             warn!("Invalid custom block found");
                         }
@@ -576,32 +971,73 @@ pub fn build() -> Result<(), Box<dyn Error>>  {
         }
     }
 
+    let locales = load_locales(Path::new("./locales"))?;
+    let localized = !locales.is_empty();
+
     if let Some(extension_info) = extension_info {
-        let mut content = include_str!("./template.js").to_string();
+        let manifest = ExtensionManifest::new(&extension_info, &custom_blocks, &label_parts, &custom_categories, &menu_items, &settings);
+        let mut manifest_file = File::create("./extension.json")?;
+        manifest_file.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+
+        for format in &options.docs {
+            let docs = match format {
+                DocFormat::Markdown => generate_markdown(&manifest),
+                DocFormat::Html => generate_html(&manifest),
+            };
+            let mut docs_file = File::create(Path::new(".").join(format.file_name()))?;
+            docs_file.write_all(docs.as_bytes())?;
+        }
 
+        let mut content = match &options.template {
+            Some(path) => JsSource::File(path.clone()).read()?,
+            None => include_str!("./template.js").to_string(),
+        };
+
+        for placeholder in REQUIRED_PLACEHOLDERS {
+            if !content.contains(placeholder) {
+                bail!("Template is missing required placeholder {placeholder}");
+            }
+        }
+
+        // First, since the helpers use the other placeholders too
+        content = content.replace("$HELPERS", include_str!("./helpers.js"));
         content = content.replace("$EXTENSION_NAME", extension_info.name);
         let extension_name_no_spaces = extension_info.name.replace(" ", "");
         content = content.replace("$NO_SPACE_EXTENSION_NAME", extension_name_no_spaces.as_str());
 
+        // Only categories declared by this extension, since translating built-in ones would change them for every extension
+        let category_names = custom_categories.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        let locales_string = locales_js(&locales, &custom_blocks, &category_names, &settings, &menu_items)?;
+        replace_feature_placeholder(&mut content, "$LOCALES", &locales_string)?;
+
         let mut menu_string = "".to_string();
 
-        for (label, fn_name) in menu_items {
-            write!(menu_string, "\t\t\t\t'{label}': window.{extension_name_no_spaces}_fns.{fn_name},\n").unwrap();
+        for (label, fn_name) in &menu_items {
+            if localized {
+                writeln!(menu_string, "\t\t\t\t[{extension_name_no_spaces}_translate('menu', '{label}', '{label}')]: window.{extension_name_no_spaces}_fns.{fn_name},").unwrap();
+            } else {
+                writeln!(menu_string, "\t\t\t\t'{label}': window.{extension_name_no_spaces}_fns.{fn_name},").unwrap();
+            }
         }
 
         content = content.replace("$MENU", &menu_string);
 
         let mut settings_string = "".to_string();
 
-        for setting in settings {
-            write!(settings_string, "\t\t\t\tExtension.ExtensionSetting.createFromLocalStorage('{}', '{}', {}, '{}', '{}', {}),\n", setting.name, setting.id, setting.default_value, setting.on_hint, setting.off_hint, setting.hidden).unwrap();
+        for setting in &settings {
+            if localized {
+                let tr = |field: &str, text: &str| format!("{extension_name_no_spaces}_translate('settings', '{}.{field}', '{text}')", setting.id);
+                writeln!(settings_string, "\t\t\t\tExtension.ExtensionSetting.createFromLocalStorage({}, '{}', {}, {}, {}, {}),", tr("name", setting.name), setting.id, setting.default_value, tr("on_hint", setting.on_hint), tr("off_hint", setting.off_hint), setting.hidden).unwrap();
+            } else {
+                writeln!(settings_string, "\t\t\t\tExtension.ExtensionSetting.createFromLocalStorage('{}', '{}', {}, '{}', '{}', {}),", setting.name, setting.id, setting.default_value, setting.on_hint, setting.off_hint, setting.hidden).unwrap();
+            }
         }
 
         content = content.replace("$SETTINGS", &settings_string);
 
         let mut categories_string = "".to_string();
 
-        for (_, cat) in custom_categories {
+        for (_, cat) in &custom_categories {
             write!(categories_string, "\t\t\t\tnew Extension.Category('{}', new Color({}, {}, {})),\n", cat.name, cat.color.0, cat.color.1, cat.color.2).unwrap();
         }
 
@@ -651,27 +1087,66 @@ pub fn build() -> Result<(), Box<dyn Error>>  {
             blocks_str += format!("\t\t\t\t\t'{}',\n", block.name).as_str();
             blocks_str += format!("\t\t\t\t\t'{}',\n", serde_json::to_string(&block.block_type)?.strip_prefix("\"").unwrap().strip_suffix("\"").unwrap()).as_str();
             blocks_str += format!("\t\t\t\t\t'{}',\n", serde_json::to_string(&block.category)?.strip_prefix("\"").unwrap().strip_suffix("\"").unwrap()).as_str();
-            blocks_str += format!("\t\t\t\t\t'{}',\n", block.spec).as_str();
-            blocks_str += format!("\t\t\t\t\t{},\n", block.defaults).as_str();
+            if localized {
+                // Spec, defaults and function are picked for the current language together, since slots may be reordered
+                writeln!(blocks_str, "\t\t\t\t\t...{extension_name_no_spaces}_localizeBlock('{}', '{}', {},", block.name, block.spec, block.defaults).unwrap();
+            } else {
+                blocks_str += format!("\t\t\t\t\t'{}',\n", block.spec).as_str();
+                blocks_str += format!("\t\t\t\t\t{},\n", block.defaults).as_str();
+            }
 
             let label_parts_str = label_parts_regex.captures_iter(block.spec).enumerate().map(|(i, _)| format!("v{i}")).collect::<Vec<_>>().join(", ");
             let fn_args_str = label_parts_regex.captures_iter(block.spec).enumerate().map(|(i, s)| {
-                if is_mult.is_match(s.get(0).unwrap().as_str()) {
+                if let Some(array_type) = block.array_params.get(i).copied().flatten() {
+                    let convert = match array_type {
+                        ArrayType::Bytes => "toBytes",
+                        ArrayType::Numbers => "toNumbers",
+                    };
+                    format!("{extension_name_no_spaces}_{convert}(v{i})")
+                } else if is_mult.is_match(s.get(0).unwrap().as_str()) {
                     format!("v{i}.contents")
+                } else if RING_LABEL_PARTS.contains(&s.get(2).unwrap().as_str()) {
+                    format!("{{ ring: v{i}, process: this }}") // Rings are run by the calling process
+                } else if SCRIPT_LABEL_PARTS.contains(&s.get(2).unwrap().as_str()) {
+                    format!("{{ ring: {extension_name_no_spaces}_script(this, v{i}), process: this }}")
+                } else if UPVAR_LABEL_PARTS.contains(&s.get(2).unwrap().as_str()) {
+                    format!("{{ upvar: v{i}, variables: this.context.outerContext.variables }}") // Set in the calling script's scope
                 } else {
                     format!("v{i}")
                 }
             }).collect::<Vec<_>>().join(", ");
 
             let proc_token = if block.pass_proc { "this, " } else { "" };
+            let cancel_token = if block.pass_token { "token, " } else { "" };
+            let timeout_token = block.timeout_ms.map(|x| format!(", {x}")).unwrap_or_default();
             let terminal_token = if block.block_type == BlockType::Terminator { ".terminal()" } else { "" };
-
-            write!(blocks_str, "\t\t\t\t\tfunction ({label_parts_str}) {{ return window.{extension_name_no_spaces}_fns.{}({proc_token}{fn_args_str}); }}\n", block.impl_fn).unwrap();
+            let array_return_token = if block.array_return.is_some() { format!(".then({extension_name_no_spaces}_fromArray)") } else { String::new() };
+
+            match block.js_impl {
+                Some(js) => writeln!(blocks_str, "\t\t\t\t\t{js}").unwrap(),
+                None if block.worker => writeln!(blocks_str, "\t\t\t\t\tfunction ({label_parts_str}) {{ return {extension_name_no_spaces}_await(this, token => {extension_name_no_spaces}_worker('{}', [{fn_args_str}], token){array_return_token}{timeout_token}); }}", block.impl_fn).unwrap(),
+                None if block.is_async => writeln!(blocks_str, "\t\t\t\t\tfunction ({label_parts_str}) {{ return {extension_name_no_spaces}_await(this, token => window.{extension_name_no_spaces}_fns.{}({proc_token}{cancel_token}{fn_args_str}){array_return_token}{timeout_token}); }}", block.impl_fn).unwrap(),
+                None if block.array_return.is_some() => writeln!(blocks_str, "\t\t\t\t\tfunction ({label_parts_str}) {{ return {extension_name_no_spaces}_fromArray(window.{extension_name_no_spaces}_fns.{}({proc_token}{fn_args_str})); }}", block.impl_fn).unwrap(),
+                None => writeln!(blocks_str, "\t\t\t\t\tfunction ({label_parts_str}) {{ return window.{extension_name_no_spaces}_fns.{}({proc_token}{fn_args_str}); }}", block.impl_fn).unwrap(),
+            }
+            if localized {
+                blocks_str += "\t\t\t\t\t)\n";
+            }
             write!(&mut blocks_str, "\t\t\t\t){terminal_token}.for(SpriteMorph, StageMorph),\n").unwrap();
         }
 
         content = content.replace("$BLOCKS", blocks_str.as_str());
 
+        let mut help_string = "".to_string();
+
+        for (_, block) in &custom_blocks {
+            if let Some(help) = block.help {
+                writeln!(help_string, "\t\t'{}': {},", block.name, serde_json::to_string(help)?).unwrap();
+            }
+        }
+
+        replace_feature_placeholder(&mut content, "$BLOCK_HELP", &help_string)?;
+
         let mut label_parts_string = "".to_string();
 
         for (_, label_part) in label_parts {
@@ -702,6 +1177,9 @@ pub fn build() -> Result<(), Box<dyn Error>>  {
         package = package.replace("-", "_");
         content = content.replace("$PACKAGE_NAME", package.as_str());
 
+        // Injected last so user code is never treated as a placeholder
+        apply_injections(&mut content, &options.injections)?;
+
         let mut out_file = File::create("./index.js")?;
         out_file.write_all(content.as_bytes())?;
 
@@ -710,4 +1188,137 @@ pub fn build() -> Result<(), Box<dyn Error>>  {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injections_replace_placeholders() {
+        let mut content = "ctor($INJECT_CONSTRUCTOR) init(`$INJECT_AFTER_INIT`)".to_string();
+        let injections = [
+            (InjectionPoint::Constructor, JsSource::Inline("this.x = 1;".into())),
+            (InjectionPoint::AfterInit, JsSource::Inline("console.log(`${x}`);".into())),
+        ];
+        apply_injections(&mut content, &injections).unwrap();
+        assert_eq!(content, "ctor(this.x = 1;\n) init(`console.log(\\`\\${x}\\`);\n`)");
+    }
+
+    #[test]
+    fn injections_require_placeholder() {
+        let mut content = "ctor($INJECT_CONSTRUCTOR)".to_string();
+        let injections = [(InjectionPoint::Methods, JsSource::Inline("foo() {}".into()))];
+        let e = apply_injections(&mut content, &injections).unwrap_err();
+        assert_eq!(e.to_string(), "Template is missing placeholder $INJECT_METHODS required by injected Methods code");
+    }
+
+    #[test]
+    fn unused_placeholders_are_removed() {
+        let mut content = "ctor($INJECT_CONSTRUCTOR)".to_string();
+        apply_injections(&mut content, &[]).unwrap();
+        assert_eq!(content, "ctor()");
+    }
+
+    #[test]
+    fn help_escapes_are_processed() {
+        let item: ItemFn = syn::parse_quote! {
+            /// Docs
+            #[netsblox_extension_block(name = "helpful", category = "operators", spec = "helpful", help = "Say \"hi\"\nthen \\ leave")]
+            pub fn helpful() {}
+        };
+        assert_eq!(recreate_netsblox_extension_block(&item, &item.attrs[1]).help, Some("Say \"hi\"\nthen \\ leave"));
+
+        let item: ItemFn = syn::parse_quote! {
+            /// Docs
+            #[netsblox_extension_block(name = "documented", category = "operators", spec = "documented")]
+            pub fn documented() {}
+        };
+        assert_eq!(recreate_netsblox_extension_block(&item, &item.attrs[1]).help, Some("Docs"));
+    }
+
+    #[test]
+    fn return_types_drop_results() {
+        let item: ItemFn = syn::parse_quote! {
+            #[netsblox_extension_block(name = "fallible", category = "operators", spec = "fallible")]
+            pub fn fallible() -> Result<Vec<f64>, HandleError> { Ok(vec![]) }
+        };
+        let block = recreate_netsblox_extension_block(&item, &item.attrs[0]);
+        assert_eq!(block.return_type, Some("Vec<f64>"));
+        assert_eq!(block.array_return, Some(ArrayType::Numbers));
+
+        let item: ItemFn = syn::parse_quote! {
+            #[netsblox_extension_block(name = "command", category = "operators", spec = "command")]
+            pub fn command() -> Result<(), JsValue> { Ok(()) }
+        };
+        assert_eq!(recreate_netsblox_extension_block(&item, &item.attrs[0]).return_type, None);
+    }
+
+    #[test]
+    fn array_types_are_detected() {
+        let array_type = |ty: syn::Type| extract_array_type(&ty);
+        assert_eq!(array_type(syn::parse_quote!(Vec<u8>)), Some(ArrayType::Bytes));
+        assert_eq!(array_type(syn::parse_quote!(&[u8])), Some(ArrayType::Bytes));
+        assert_eq!(array_type(syn::parse_quote!(std::vec::Vec<f64>)), Some(ArrayType::Numbers));
+        assert_eq!(array_type(syn::parse_quote!(&'a [f64])), Some(ArrayType::Numbers));
+        assert_eq!(array_type(syn::parse_quote!(&mut [u8])), None);
+        assert_eq!(array_type(syn::parse_quote!(Vec<f32>)), None);
+        assert_eq!(array_type(syn::parse_quote!([u8; 4])), None);
+        assert_eq!(array_type(syn::parse_quote!(Vec)), None);
+    }
+
+    // Runs a script after the JS helpers with node, reporting what it prints as JSON
+    fn run_helpers(script: &str) -> serde_json::Value {
+        let helpers = include_str!("./helpers.js").replace("$NO_SPACE_EXTENSION_NAME", "X");
+        let output = std::process::Command::new("node").arg("-e").arg(format!("{helpers}\n{script}")).output().expect("node is needed to test the JS helpers");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+
+    #[test]
+    fn saved_state_round_trips_through_xml() {
+        let res = run_helpers(r#"
+            // Escaped with a replacement function, like NetsBlox's serializer does
+            const escape = x => x.replace(/[&<>]/g, c => ({ '&': '&amp;', '<': '&lt;', '>': '&gt;' })[c]);
+            const unescape = x => x.replace(/&(amp|lt|gt);/g, (_, c) => ({ amp: '&', lt: '<', gt: '>' })[c]);
+            const state = JSON.stringify({ text: "costs $$5 and $& or $' and $`" });
+            const xml = X_insertState('<stage name="Stage"><sprites></sprites></stage>', `<rust-state extension="X">${escape(state)}</rust-state>`);
+            const contents = xml.match(/^<stage name="Stage"><sprites><\/sprites><rust-state extension="X">(.*)<\/rust-state><\/stage>$/)[1];
+            console.log(JSON.stringify([unescape(contents) === state, X_insertState('<project></project>', 'state')]));
+        "#);
+        assert_eq!(res, serde_json::json!([true, "<project></project>"]));
+    }
+
+    #[test]
+    fn array_helpers_parse_input() {
+        let res = run_helpers(r#"
+            class List { constructor(items) { this.items = items; } itemsArray() { return this.items; } }
+            const attempt = f => { try { return Array.from(f()); } catch (e) { return e.message; } };
+            console.log(JSON.stringify([
+                attempt(() => X_toBytes('AQL/')),
+                attempt(() => X_toBytes('data:application/octet-stream;base64,AQL/')),
+                attempt(() => X_toBytes(new List([1, '2', 255]))),
+                attempt(() => X_toBytes('')),
+                attempt(() => X_toBytes('not base64!')),
+                attempt(() => X_toBytes(new List([1, 256]))),
+                attempt(() => X_toBytes(new List([1, ' ']))),
+                attempt(() => X_toBytes(5)),
+                attempt(() => X_toNumbers(new List(['1.5', -2]))),
+                attempt(() => X_toNumbers(new List(['1', '  ']))),
+                attempt(() => X_toNumbers(new List([true]))),
+            ]));
+        "#);
+        assert_eq!(res, serde_json::json!([
+            [1, 2, 255],
+            [1, 2, 255],
+            [1, 2, 255],
+            [],
+            "expected a list of bytes or base64 text",
+            "item 2: expected a byte from 0 to 255, got 256",
+            "item 2: expected a byte from 0 to 255, got  ",
+            "expected a list of bytes or base64 text",
+            [1.5, -2],
+            "item 2: expected a number, got   ",
+            "item 1: expected a number, got true",
+        ]));
+    }
+}
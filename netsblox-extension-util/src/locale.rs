@@ -0,0 +1,186 @@
+use std::{collections::BTreeMap, error::Error, fs::{self, File}, io::Read, path::Path, fmt::Write as FmtWrite};
+
+use regex::Regex;
+use serde::Deserialize;
+use simple_error::bail;
+
+use crate::{CustomBlock, ExtensionSetting};
+
+/// Translations for one language, read from locales/<language code>.json
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Locale {
+    /// Translated specs, keyed by block name
+    #[serde(default)]
+    pub blocks: BTreeMap<String, BlockTranslation>,
+    /// Translated category names, keyed by category name
+    #[serde(default)]
+    pub categories: BTreeMap<String, String>,
+    /// Translated setting text, keyed by setting id
+    #[serde(default)]
+    pub settings: BTreeMap<String, SettingTranslation>,
+    /// Translated menu item labels, keyed by label
+    #[serde(default)]
+    pub menu: BTreeMap<String, String>,
+}
+
+/// A translated block spec, with the slots in any order
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum BlockTranslation {
+    Spec(String),
+    /// For each slot of the translated spec, `order` gives the index of the matching slot in the original spec.
+    /// Only needed when slots of the same type swap places, otherwise the order is inferred.
+    Ordered { spec: String, order: Option<Vec<usize>> },
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SettingTranslation {
+    pub name: Option<String>,
+    pub on_hint: Option<String>,
+    pub off_hint: Option<String>,
+}
+
+/// Read every locales/*.json file in the directory, keyed by language code
+pub fn load_locales(dir: &Path) -> Result<BTreeMap<String, Locale>, Box<dyn Error>> {
+    let mut res = BTreeMap::new();
+    if !dir.is_dir() {
+        return Ok(res);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|x| x == "json") {
+            let lang = path.file_stem().unwrap().to_string_lossy().to_string();
+            let mut content = String::new();
+            File::open(&path)?.read_to_string(&mut content)?;
+            let locale: Locale = serde_json::from_str(&content).map_err(|e| format!("invalid locale file {}: {e}", path.display()))?;
+            res.insert(lang, locale);
+        }
+    }
+
+    Ok(res)
+}
+
+// A translated spec and, if its slots are reordered, the original index of each slot
+type SlotOrder = (String, Option<Vec<usize>>);
+
+// Find which original slot each slot of the translated spec corresponds to
+fn slot_order(block: &CustomBlock, lang: &str, translation: &BlockTranslation) -> Result<SlotOrder, Box<dyn Error>> {
+    let label_parts_regex = Regex::new(r"(%mult)?%(\w+)")?;
+    let (spec, order) = match translation {
+        BlockTranslation::Spec(spec) => (spec, None),
+        BlockTranslation::Ordered { spec, order } => (spec, order.clone()),
+    };
+
+    let original = label_parts_regex.find_iter(block.spec).map(|x| x.as_str()).collect::<Vec<_>>();
+    let translated = label_parts_regex.find_iter(spec).map(|x| x.as_str()).collect::<Vec<_>>();
+    if original.len() != translated.len() {
+        bail!("{lang} translation of block {} has {} slots, expected {}", block.name, translated.len(), original.len());
+    }
+
+    let order = match order {
+        Some(order) => {
+            let mut seen = vec![false; original.len()];
+            if order.len() != original.len() {
+                bail!("{lang} translation of block {} has {} entries in order, expected {}", block.name, order.len(), original.len());
+            }
+            for (i, &j) in order.iter().enumerate() {
+                if j >= original.len() || seen[j] || original[j] != translated[i] {
+                    bail!("{lang} translation of block {} has an invalid order entry {j} for slot {}", block.name, translated[i]);
+                }
+                seen[j] = true;
+            }
+            order
+        }
+        None => {
+            // Match each slot with the first unused original slot of the same type
+            let mut used = vec![false; original.len()];
+            let mut order = vec![];
+            for slot in &translated {
+                match (0..original.len()).find(|&j| !used[j] && original[j] == *slot) {
+                    Some(j) => {
+                        used[j] = true;
+                        order.push(j);
+                    }
+                    None => bail!("{lang} translation of block {} has unknown slot {slot}", block.name),
+                }
+            }
+            order
+        }
+    };
+
+    let identity = order.iter().enumerate().all(|(i, &j)| i == j);
+    Ok((spec.clone(), if identity { None } else { Some(order) }))
+}
+
+/// Generate the JS translation table, checking that every translation refers to something in the extension
+pub fn locales_js(locales: &BTreeMap<String, Locale>, blocks: &[(String, CustomBlock)], categories: &[&str], settings: &[ExtensionSetting], menu_items: &[(String, String)]) -> Result<String, Box<dyn Error>> {
+    let mut res = String::new();
+
+    for (lang, locale) in locales {
+        let mut block_specs = serde_json::Map::new();
+        for (name, translation) in &locale.blocks {
+            let Some((_, block)) = blocks.iter().find(|(b, _)| b == name) else {
+                bail!("{lang} translation for unknown block {name}");
+            };
+            let (spec, order) = slot_order(block, lang, translation)?;
+            block_specs.insert(name.clone(), serde_json::json!([spec, order]));
+        }
+
+        for name in locale.categories.keys() {
+            if !categories.contains(&name.as_str()) {
+                bail!("{lang} translation for unknown category {name}, only categories declared by the extension can be translated");
+            }
+        }
+        for id in locale.settings.keys() {
+            if !settings.iter().any(|x| x.id == id) {
+                bail!("{lang} translation for unknown setting {id}");
+            }
+        }
+        for label in locale.menu.keys() {
+            if !menu_items.iter().any(|(x, _)| x == label) {
+                bail!("{lang} translation for unknown menu item {label}");
+            }
+        }
+
+        // Settings are flattened to "id.field" keys so all lookups share one helper
+        let mut setting_text = BTreeMap::new();
+        for (id, setting) in &locale.settings {
+            for (field, text) in [("name", &setting.name), ("on_hint", &setting.on_hint), ("off_hint", &setting.off_hint)] {
+                if let Some(text) = text {
+                    setting_text.insert(format!("{id}.{field}"), text);
+                }
+            }
+        }
+
+        writeln!(res, "\t\t{}: {{", serde_json::to_string(lang)?).unwrap();
+        writeln!(res, "\t\t\tblocks: {},", serde_json::to_string(&block_specs)?).unwrap();
+        writeln!(res, "\t\t\tcategories: {},", serde_json::to_string(&locale.categories)?).unwrap();
+        writeln!(res, "\t\t\tsettings: {},", serde_json::to_string(&setting_text)?).unwrap();
+        writeln!(res, "\t\t\tmenu: {},", serde_json::to_string(&locale.menu)?).unwrap();
+        res.push_str("\t\t},\n");
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locales(json: &str) -> BTreeMap<String, Locale> {
+        BTreeMap::from([("es".to_string(), serde_json::from_str(json).unwrap())])
+    }
+
+    #[test]
+    fn translates_declared_categories() {
+        let res = locales_js(&locales(r#"{ "categories": { "Hello World": "Hola Mundo" } }"#), &[], &["Hello World"], &[], &[]).unwrap();
+        assert!(res.contains(r#"categories: {"Hello World":"Hola Mundo"},"#));
+    }
+
+    #[test]
+    fn rejects_builtin_categories() {
+        let e = locales_js(&locales(r#"{ "categories": { "operators": "operadores" } }"#), &[], &["Hello World"], &[], &[]).unwrap_err();
+        assert_eq!(e.to_string(), "es translation for unknown category operators, only categories declared by the extension can be translated");
+    }
+}
@@ -0,0 +1,178 @@
+use serde::Serialize;
+use regex::Regex;
+
+use crate::{BlockType, CustomBlock, CustomCategory, ExtensionInfo, ExtensionSetting, LabelPart};
+
+/// Machine-readable description of an extension's interface, written to extension.json
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtensionManifest<'a> {
+    pub info: &'a ExtensionInfo,
+    pub categories: Vec<&'a CustomCategory>,
+    pub settings: &'a [ExtensionSetting],
+    pub menu_items: Vec<ManifestMenuItem<'a>>,
+    pub label_parts: Vec<&'a LabelPart>,
+    pub blocks: Vec<ManifestBlock<'a>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestMenuItem<'a> {
+    pub label: &'a str,
+    pub impl_fn: &'a str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestBlock<'a> {
+    #[serde(flatten)]
+    pub block: &'a CustomBlock,
+    pub terminal: bool,
+    pub slots: Vec<Slot<'a>>,
+}
+
+/// An input slot in a block spec
+#[derive(Debug, Clone, Serialize)]
+pub struct Slot<'a> {
+    /// Label part name, without the leading %
+    pub label_part: &'a str,
+    /// Whether the slot was declared with %mult and takes a variable number of inputs
+    pub variadic: bool,
+    /// Definition of the label part, if it is not built into NetsBlox
+    pub custom: Option<&'a LabelPart>,
+}
+
+/// Find the input slots of a block spec, in order
+pub fn block_slots<'a>(spec: &'a str, label_parts: &[&'a LabelPart]) -> Vec<Slot<'a>> {
+    let label_parts_regex = Regex::new(r"(%mult)?%(\w+)").unwrap();
+
+    label_parts_regex.captures_iter(spec).map(|cap| {
+        let label_part = cap.get(2).unwrap().as_str();
+        Slot {
+            label_part,
+            variadic: cap.get(1).is_some(),
+            custom: label_parts.iter().find(|x| x.spec == label_part).copied(),
+        }
+    }).collect()
+}
+
+impl<'a> ExtensionManifest<'a> {
+    pub fn new(info: &'a ExtensionInfo, custom_blocks: &'a [(String, CustomBlock)], label_parts: &'a [(&str, LabelPart)], custom_categories: &'a [(String, CustomCategory)], menu_items: &'a [(String, String)], settings: &'a [ExtensionSetting]) -> Self {
+        let label_parts = label_parts.iter().map(|(_, x)| x).collect::<Vec<_>>();
+
+        let blocks = custom_blocks.iter().map(|(_, block)| ManifestBlock {
+            block,
+            terminal: block.block_type == BlockType::Terminator,
+            slots: block_slots(block.spec, &label_parts),
+        }).collect();
+
+        Self {
+            info,
+            categories: custom_categories.iter().map(|(_, x)| x).collect(),
+            settings,
+            menu_items: menu_items.iter().map(|(label, impl_fn)| ManifestMenuItem { label, impl_fn }).collect(),
+            label_parts,
+            blocks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{recreate_netsblox_extension_block, recreate_netsblox_extension_js_block, Menu};
+
+    const SHAPES: &[Menu] = &[
+        Menu::Entry { label: "circle", value: "circle" },
+        Menu::Submenu { label: "polygon", content: &[Menu::Entry { label: "square", value: "square" }] },
+    ];
+
+    #[test]
+    fn manifest_describes_blocks_and_slots() {
+        let info = ExtensionInfo { name: "Test" };
+        let label_parts = [("shape", LabelPart { spec: "shape", text: Some("circle"), numeric: false, menu: Some(SHAPES), readonly: true })];
+        let draw: syn::ItemFn = syn::parse_quote! {
+            #[netsblox_extension_block(name = "drawShapes", category = "pen", spec = "draw %shape at %mult%n", block_type = BlockType::Terminator)]
+            pub fn draw_shapes(shape: &str, points: Vec<f64>) {}
+        };
+        let upper: syn::ItemConst = syn::parse_quote! {
+            #[netsblox_extension_js_block(name = "jsUppercase", category = "operators", spec = "uppercase %s", block_type = BlockType::Reporter)]
+            const JS_UPPERCASE: &str = "function (text) { return text.toUpperCase(); }";
+        };
+        let blocks = [
+            ("drawShapes".to_owned(), recreate_netsblox_extension_block(&draw, &draw.attrs[0])),
+            ("jsUppercase".to_owned(), recreate_netsblox_extension_js_block(&upper, &upper.attrs[0])),
+        ];
+        let manifest = ExtensionManifest::new(&info, &blocks, &label_parts, &[], &[], &[]);
+
+        let shape = serde_json::json!({
+            "spec": "shape",
+            "text": "circle",
+            "numeric": false,
+            "menu": [
+                { "Entry": { "label": "circle", "value": "circle" } },
+                { "Submenu": { "label": "polygon", "content": [{ "Entry": { "label": "square", "value": "square" } }] } },
+            ],
+            "readonly": true,
+        });
+        assert_eq!(serde_json::to_value(&manifest).unwrap(), serde_json::json!({
+            "info": { "name": "Test" },
+            "categories": [],
+            "settings": [],
+            "menu_items": [],
+            "label_parts": [shape],
+            "blocks": [
+                {
+                    "name": "drawShapes",
+                    "block_type": "command",
+                    "category": "pen",
+                    "spec": "draw %shape at %mult%n",
+                    "defaults": "[]",
+                    "impl_fn": "draw_shapes",
+                    "js_impl": null,
+                    "target": "Both",
+                    "pass_proc": false,
+                    "pad_top": false,
+                    "pad_bottom": false,
+                    "doc": null,
+                    "return_type": null,
+                    "help": null,
+                    "is_async": false,
+                    "worker": false,
+                    "pass_token": false,
+                    "timeout_ms": null,
+                    "array_params": [null, "Numbers"],
+                    "array_return": null,
+                    "terminal": true,
+                    "slots": [
+                        { "label_part": "shape", "variadic": false, "custom": shape },
+                        { "label_part": "n", "variadic": true, "custom": null },
+                    ],
+                },
+                {
+                    "name": "jsUppercase",
+                    "block_type": "reporter",
+                    "category": "operators",
+                    "spec": "uppercase %s",
+                    "defaults": "[]",
+                    "impl_fn": "JS_UPPERCASE",
+                    "js_impl": "function (text) { return text.toUpperCase(); }",
+                    "target": "Both",
+                    "pass_proc": false,
+                    "pad_top": false,
+                    "pad_bottom": false,
+                    "doc": null,
+                    "return_type": null,
+                    "help": null,
+                    "is_async": false,
+                    "worker": false,
+                    "pass_token": false,
+                    "timeout_ms": null,
+                    "array_params": [],
+                    "array_return": null,
+                    "terminal": false,
+                    "slots": [
+                        { "label_part": "s", "variadic": false, "custom": null },
+                    ],
+                },
+            ],
+        }));
+    }
+}
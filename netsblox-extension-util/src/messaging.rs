@@ -0,0 +1,255 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use js_sys::{JsString, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::Value;
+
+#[wasm_bindgen(inline_js = r#"
+export function sendSocketMessage(target, msgType, content) {
+    const ide = world.children[0];
+    ide.sockets.sendMessage({ type: 'message', dstId: target, srcId: ide.sockets.getClientAddress?.(), msgType, content });
+}
+
+export function listenForMessages(callback) {
+    const handlers = WebSocketManager.MessageHandlers;
+    const original = handlers.message;
+    handlers.message = function (msg) {
+        callback(msg.msgType, msg.content ?? {}, msg.srcId ?? '');
+        return original.call(this, msg);
+    };
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = sendSocketMessage, catch)]
+    fn send_socket_message(target: &str, msg_type: &str, content: &Object) -> Result<(), JsValue>;
+    #[wasm_bindgen(js_name = listenForMessages, catch)]
+    fn listen_for_messages(callback: &Closure<dyn Fn(String, JsValue, String)>) -> Result<(), JsValue>;
+}
+
+/// A NetsBlox message: a message type and the values of its fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub msg_type: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Message {
+    pub fn new(msg_type: impl Into<String>) -> Self {
+        Self { msg_type: msg_type.into(), fields: vec![] }
+    }
+
+    /// Add a field, e.g. `Message::new("chat").with_field("text", "hi")`
+    pub fn with_field(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.fields.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.fields.iter().find(|(x, _)| x == name).map(|(_, x)| x)
+    }
+
+    fn content(&self) -> Object {
+        let content = Object::new();
+        for (name, value) in &self.fields {
+            Reflect::set(&content, &name.as_str().into(), &value.to_js()).unwrap();
+        }
+        content
+    }
+
+    fn from_content(msg_type: String, content: &JsValue) -> Self {
+        let fields = Object::entries(&content.clone().unchecked_into()).iter().filter_map(|entry| {
+            let name = Reflect::get(&entry, &0.into()).ok()?.as_string()?;
+            let value = Reflect::get(&entry, &1.into()).ok()?;
+            // Values without a plain equivalent, e.g. costumes, are passed on as their text like NetsBlox shows them
+            Some((name, Value::from_js(&value).unwrap_or_else(|_| Value::Text(String::from(JsString::from(value))))))
+        }).collect();
+        Self { msg_type, fields }
+    }
+}
+
+/// Delivers messages sent from Rust, see [`set_transport`]
+pub trait MessageTransport {
+    /// Send a message to a role name or address, e.g. `"player2"` or `"player2@game@owner"`
+    fn send(&self, target: &str, message: &Message) -> Result<(), JsValue>;
+
+    /// Called whenever the transport is set, to start passing incoming messages to [`receive_message`]
+    fn listen(&self) -> Result<(), JsValue> {
+        Ok(())
+    }
+
+    /// Called with messages from the NetsBlox cloud while this is the current transport, which ignores them by default.
+    ///
+    /// The cloud is only listened to once, so switching transports doesn't deliver its messages twice.
+    fn receive_cloud_message(&self, _source: &str, _message: &Message) {}
+}
+
+/// Sends messages through the IDE's connection to the NetsBlox cloud. This is the default transport.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NetsBloxTransport;
+
+impl MessageTransport for NetsBloxTransport {
+    fn send(&self, target: &str, message: &Message) -> Result<(), JsValue> {
+        send_socket_message(target, &message.msg_type, &message.content())
+    }
+
+    fn listen(&self) -> Result<(), JsValue> {
+        if MESSAGING.with_borrow(|x| x.listening) {
+            return Ok(());
+        }
+
+        let callback = Closure::<dyn Fn(String, JsValue, String)>::new(|msg_type: String, content: JsValue, source: String| {
+            let message = Message::from_content(msg_type, &content);
+            if let Some(transport) = MESSAGING.with_borrow(|x| x.transport.clone()) {
+                transport.receive_cloud_message(&source, &message);
+            }
+        });
+        listen_for_messages(&callback)?;
+        callback.forget();
+        MESSAGING.with_borrow_mut(|x| x.listening = true);
+        Ok(())
+    }
+
+    fn receive_cloud_message(&self, source: &str, message: &Message) {
+        receive_message(source, message);
+    }
+}
+
+/// A stand-in transport for testing without the cloud, which also works natively, e.g. in `cargo test`.
+///
+/// Sent messages are recorded and delivered straight back to this extension's handlers, as if sent to the current role.
+/// Messages from the cloud are ignored while it is in use.
+#[derive(Debug, Default, Clone)]
+pub struct LocalTransport {
+    sent: Rc<RefCell<Vec<(String, Message)>>>,
+}
+
+impl LocalTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every message sent so far, with the target it was sent to
+    pub fn sent(&self) -> Vec<(String, Message)> {
+        self.sent.borrow().clone()
+    }
+}
+
+impl MessageTransport for LocalTransport {
+    fn send(&self, target: &str, message: &Message) -> Result<(), JsValue> {
+        self.sent.borrow_mut().push((target.to_string(), message.clone()));
+        receive_message("local", message);
+        Ok(())
+    }
+}
+
+type MessageHandler = Rc<dyn Fn(&Message, &str)>;
+
+#[derive(Default)]
+struct Messaging {
+    transport: Option<Rc<dyn MessageTransport>>,
+    // Whether the NetsBlox cloud listener is installed
+    listening: bool,
+    handlers: HashMap<String, Vec<MessageHandler>>,
+}
+
+thread_local! {
+    static MESSAGING: RefCell<Messaging> = RefCell::new(Messaging::default());
+}
+
+fn transport() -> Result<Rc<dyn MessageTransport>, JsValue> {
+    if let Some(transport) = MESSAGING.with_borrow(|x| x.transport.clone()) {
+        return Ok(transport);
+    }
+    set_transport(NetsBloxTransport)?;
+    Ok(MESSAGING.with_borrow(|x| x.transport.clone()).unwrap())
+}
+
+/// Replace the transport messages are sent and received through, e.g. with a [`LocalTransport`] in tests
+pub fn set_transport(transport: impl MessageTransport + 'static) -> Result<(), JsValue> {
+    transport.listen()?;
+    MESSAGING.with_borrow_mut(|x| x.transport = Some(Rc::new(transport)));
+    Ok(())
+}
+
+/// Send a message to a role name or address
+pub fn send_message(target: &str, message: &Message) -> Result<(), JsValue> {
+    transport()?.send(target, message)
+}
+
+/// Call `handler` with every incoming message of the given type and the address it was sent from
+pub fn on_message(msg_type: &str, handler: impl Fn(&Message, &str) + 'static) -> Result<(), JsValue> {
+    transport()?;
+    MESSAGING.with_borrow_mut(|x| x.handlers.entry(msg_type.to_string()).or_default().push(Rc::new(handler)));
+    Ok(())
+}
+
+/// Pass an incoming message to the handlers for its type. Transports call this for every message they receive.
+pub fn receive_message(source: &str, message: &Message) {
+    // Cloned out of the store so handlers can send messages and register handlers themselves
+    let handlers = MESSAGING.with_borrow(|x| x.handlers.get(&message.msg_type).cloned().unwrap_or_default());
+    for handler in handlers {
+        handler(message, source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn local_transport_delivers_to_handlers() {
+        let transport = LocalTransport::new();
+        set_transport(transport.clone()).unwrap();
+
+        let received = Rc::new(RefCell::new(vec![]));
+        let log = received.clone();
+        on_message("chat", move |msg, source| log.borrow_mut().push((source.to_string(), msg.field("text").cloned()))).unwrap();
+
+        let message = Message::new("chat").with_field("text", "hello").with_field("count", 2);
+        send_message("player2", &message).unwrap();
+        send_message("player2", &Message::new("other")).unwrap();
+
+        assert_eq!(*received.borrow(), [("local".to_string(), Some(Value::Text("hello".into())))]);
+        assert_eq!(transport.sent(), [("player2".to_string(), message), ("player2".to_string(), Message::new("other"))]);
+    }
+
+    #[test]
+    fn switching_local_transports_delivers_once() {
+        set_transport(LocalTransport::new()).unwrap();
+        set_transport(LocalTransport::new()).unwrap();
+
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+        on_message("ping", move |_, _| counter.set(counter.get() + 1)).unwrap();
+        send_message("me", &Message::new("ping")).unwrap();
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn cloud_listener_is_installed_once() {
+        // Installing the listener needs the IDE, so an installed listener is simulated
+        MESSAGING.with_borrow_mut(|x| x.listening = true);
+        set_transport(NetsBloxTransport).unwrap();
+        set_transport(NetsBloxTransport).unwrap();
+        assert!(MESSAGING.with_borrow(|x| x.listening));
+    }
+
+    #[test]
+    fn cloud_messages_follow_the_current_transport() {
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+        set_transport(LocalTransport::new()).unwrap();
+        on_message("ping", move |_, _| counter.set(counter.get() + 1)).unwrap();
+
+        let deliver = || MESSAGING.with_borrow(|x| x.transport.clone()).unwrap().receive_cloud_message("cloud", &Message::new("ping"));
+        deliver();
+        assert_eq!(count.get(), 0);
+
+        MESSAGING.with_borrow_mut(|x| x.transport = Some(Rc::new(NetsBloxTransport)));
+        deliver();
+        assert_eq!(count.get(), 1);
+    }
+}
@@ -0,0 +1,173 @@
+use std::borrow::Cow;
+
+use js_sys::Reflect;
+use wasm_bindgen::{prelude::*, Clamped};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+
+use crate::{Image, Stage};
+
+/// Draws on the stage's pen trails layer, see [`Stage::draw`].
+///
+/// Positions are in stage coordinates, with (0, 0) at the center and y pointing up, and colors are CSS colors such as
+/// `"red"` or `"rgba(0, 0, 255, 0.5)"`.
+pub struct PenTrails {
+    ctx: CanvasRenderingContext2d,
+    width: f64,
+    height: f64,
+}
+
+impl PenTrails {
+    /// Convert stage coordinates to pixels on the pen trails canvas
+    pub fn to_canvas(&self, x: f64, y: f64) -> (f64, f64) {
+        (x + self.width / 2.0, self.height / 2.0 - y)
+    }
+
+    /// Convert pixels on the pen trails canvas to stage coordinates
+    pub fn to_stage(&self, x: f64, y: f64) -> (f64, f64) {
+        (x - self.width / 2.0, self.height / 2.0 - y)
+    }
+
+    fn path(&self, points: &[(f64, f64)]) {
+        self.ctx.begin_path();
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let (x, y) = self.to_canvas(x, y);
+            if i == 0 { self.ctx.move_to(x, y) } else { self.ctx.line_to(x, y) }
+        }
+    }
+
+    pub fn line(&self, from: (f64, f64), to: (f64, f64), width: f64, color: &str) {
+        self.polyline(&[from, to], width, color);
+    }
+
+    /// Connected line segments through the points
+    pub fn polyline(&self, points: &[(f64, f64)], width: f64, color: &str) {
+        self.path(points);
+        self.ctx.set_line_width(width);
+        self.ctx.set_line_cap("round");
+        self.ctx.set_line_join("round");
+        self.ctx.set_stroke_style_str(color);
+        self.ctx.stroke();
+    }
+
+    /// A filled polygon with the points as corners
+    pub fn polygon(&self, points: &[(f64, f64)], color: &str) {
+        self.path(points);
+        self.ctx.close_path();
+        self.ctx.set_fill_style_str(color);
+        self.ctx.fill();
+    }
+
+    /// Text with its bottom left corner at the given position
+    pub fn text(&self, pos: (f64, f64), text: &str, size: f64, color: &str) -> Result<(), JsValue> {
+        let (x, y) = self.to_canvas(pos.0, pos.1);
+        self.ctx.set_font(&format!("{size}px sans-serif"));
+        self.ctx.set_fill_style_str(color);
+        self.ctx.fill_text(text, x, y)
+    }
+
+    /// Copy an image's pixels with its top left corner at the given position, replacing what was drawn there
+    pub fn put_image(&self, pos: (f64, f64), image: &Image) -> Result<(), JsValue> {
+        // Pixels are put without the context's transform, which scales canvas pixels to device pixels on high-DPI displays
+        let transform = self.ctx.get_transform()?;
+        let scale = (transform.a(), transform.d());
+        let (x, y) = to_device(self.to_canvas(pos.0, pos.1), scale, (transform.e(), transform.f()));
+        let (width, height, pixels) = scale_pixels(image, scale);
+        let data = ImageData::new_with_u8_clamped_array_and_sh(Clamped(&pixels), width, height)?;
+        self.ctx.put_image_data(&data, x.round(), y.round())
+    }
+
+    /// Erase everything drawn with pens
+    pub fn clear(&self) {
+        self.ctx.clear_rect(0.0, 0.0, self.width, self.height);
+    }
+
+    /// The underlying canvas context, for drawing in canvas pixels
+    pub fn context(&self) -> &CanvasRenderingContext2d {
+        &self.ctx
+    }
+}
+
+// Apply a canvas context's scale and translation to a position in canvas pixels
+fn to_device((x, y): (f64, f64), (scale_x, scale_y): (f64, f64), (dx, dy): (f64, f64)) -> (f64, f64) {
+    (x * scale_x + dx, y * scale_y + dy)
+}
+
+// Nearest-neighbor scaling, so each image pixel covers the device pixels a canvas pixel does
+fn scale_pixels(image: &Image, (scale_x, scale_y): (f64, f64)) -> (u32, u32, Cow<'_, [u8]>) {
+    if (scale_x == 1.0 && scale_y == 1.0) || image.width() == 0 || image.height() == 0 {
+        return (image.width(), image.height(), Cow::Borrowed(image.data()));
+    }
+    let width = (image.width() as f64 * scale_x).round().max(1.0) as u32;
+    let height = (image.height() as f64 * scale_y).round().max(1.0) as u32;
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height {
+        let src_y = (((y as f64 + 0.5) / scale_y) as u32).min(image.height() - 1);
+        for x in 0..width {
+            let src_x = (((x as f64 + 0.5) / scale_x) as u32).min(image.width() - 1);
+            let i = (src_y * image.width() + src_x) as usize * 4;
+            pixels.extend_from_slice(&image.data()[i..i + 4]);
+        }
+    }
+    (width, height, Cow::Owned(pixels))
+}
+
+impl Stage {
+    /// Draw on the pen trails layer, redrawing the stage once `f` returns
+    pub fn draw<R>(&self, f: impl FnOnce(&PenTrails) -> R) -> Result<R, JsValue> {
+        #[cfg(feature = "testing")]
+        assert!(self.mock.is_none(), "mock stages have no pen trails");
+        let stage = self.as_js();
+        let pen_trails = Reflect::get(stage, &"penTrails".into())?.dyn_into::<js_sys::Function>()?;
+        let canvas: HtmlCanvasElement = pen_trails.call0(stage)?.dyn_into()?;
+        let ctx: CanvasRenderingContext2d = canvas.get_context("2d")?.ok_or("pen trails are not available")?.dyn_into()?;
+
+        ctx.save();
+        let res = f(&PenTrails { ctx: ctx.clone(), width: canvas.width() as f64, height: canvas.height() as f64 });
+        ctx.restore();
+
+        // Same as NetsBlox's own pen blocks, so the next frame shows the new trails
+        Reflect::set(stage, &"cachedPenTrailsMorph".into(), &JsValue::NULL)?;
+        let changed = Reflect::get(stage, &"changed".into())?.dyn_into::<js_sys::Function>()?;
+        changed.call0(stage)?;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pen_trails(width: f64, height: f64) -> PenTrails {
+        PenTrails { ctx: JsValue::UNDEFINED.unchecked_into(), width, height }
+    }
+
+    #[test]
+    fn stage_coordinates_map_to_canvas_pixels() {
+        let pen = pen_trails(480.0, 360.0);
+        assert_eq!(pen.to_canvas(0.0, 0.0), (240.0, 180.0));
+        assert_eq!(pen.to_canvas(-240.0, 180.0), (0.0, 0.0));
+        assert_eq!(pen.to_canvas(100.0, -50.0), (340.0, 230.0));
+        assert_eq!(pen.to_stage(340.0, 230.0), (100.0, -50.0));
+    }
+
+    #[test]
+    fn canvas_pixels_map_to_device_pixels() {
+        assert_eq!(to_device((240.0, 180.0), (1.0, 1.0), (0.0, 0.0)), (240.0, 180.0));
+        assert_eq!(to_device((240.0, 180.0), (2.0, 2.0), (0.0, 0.0)), (480.0, 360.0));
+        assert_eq!(to_device((10.0, 20.0), (1.5, 1.5), (3.0, -3.0)), (18.0, 27.0));
+    }
+
+    #[test]
+    fn images_are_scaled_to_device_pixels() {
+        let image = Image::new(2, 1, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let (width, height, pixels) = scale_pixels(&image, (1.0, 1.0));
+        assert!(matches!(pixels, Cow::Borrowed(_)));
+        assert_eq!((width, height), (2, 1));
+
+        let (width, height, pixels) = scale_pixels(&image, (2.0, 2.0));
+        assert_eq!((width, height), (4, 2));
+        let row = [1, 2, 3, 4, 1, 2, 3, 4, 5, 6, 7, 8, 5, 6, 7, 8];
+        assert_eq!(&pixels[..16], row);
+        assert_eq!(&pixels[16..], row);
+    }
+}
@@ -0,0 +1,128 @@
+use std::{cell::RefCell, rc::Rc};
+
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::prelude::*;
+
+struct PersistEntry {
+    key: String,
+    save: Box<dyn Fn() -> Result<serde_json::Value, serde_json::Error>>,
+    load: Rc<dyn Fn(Option<serde_json::Value>) -> Result<(), serde_json::Error>>,
+}
+
+thread_local! {
+    static PERSISTED: RefCell<Vec<PersistEntry>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Store extension state in saved projects under `key`.
+///
+/// `save` is called whenever the project is saved, and `load` whenever a role is opened, with `None` if the project
+/// has no state for this key, e.g. because it is new, so the extension can reset.
+pub fn persist<T: Serialize + DeserializeOwned + 'static>(key: &str, save: impl Fn() -> T + 'static, load: impl Fn(Option<T>) + 'static) {
+    let entry = PersistEntry {
+        key: key.to_string(),
+        save: Box::new(move || serde_json::to_value(save())),
+        load: Rc::new(move |value| {
+            load(value.map(serde_json::from_value).transpose()?);
+            Ok(())
+        }),
+    };
+    PERSISTED.with_borrow_mut(|entries| {
+        entries.retain(|x| x.key != key);
+        entries.push(entry);
+    });
+}
+
+/// Called by the generated extension when the project is saved, returning the JSON to store in it
+#[wasm_bindgen]
+pub fn netsblox_extension_save_state() -> Result<Option<String>, JsValue> {
+    save_state().map_err(|e| JsValue::from_str(&e))
+}
+
+/// Called by the generated extension when a role is opened, with the JSON stored in the project if any
+#[wasm_bindgen]
+pub fn netsblox_extension_load_state(state: Option<String>) -> Result<(), JsValue> {
+    load_state(state.as_deref()).map_err(|e| JsValue::from_str(&e))
+}
+
+fn save_state() -> Result<Option<String>, String> {
+    PERSISTED.with_borrow(|entries| {
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let mut state = serde_json::Map::new();
+        for entry in entries {
+            let value = (entry.save)().map_err(|e| format!("could not save {}: {e}", entry.key))?;
+            state.insert(entry.key.clone(), value);
+        }
+        Ok(Some(serde_json::Value::Object(state).to_string()))
+    })
+}
+
+fn load_state(state: Option<&str>) -> Result<(), String> {
+    let mut state = match state.map(serde_json::from_str::<serde_json::Map<String, serde_json::Value>>).transpose() {
+        Ok(x) => x.unwrap_or_default(),
+        Err(e) => return Err(format!("invalid extension state: {e}")),
+    };
+
+    // Cloned out of the store so `load` can register state itself
+    let loaders = PERSISTED.with_borrow(|entries| entries.iter().map(|x| (x.key.clone(), x.load.clone())).collect::<Vec<_>>());
+    let mut errors = vec![];
+    for (key, load) in loaders {
+        if let Err(e) = load(state.remove(&key)) {
+            errors.push(format!("could not load {key}: {e}"));
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::HashMap};
+
+    use super::*;
+
+    #[test]
+    fn nothing_is_saved_without_persisted_state() {
+        assert_eq!(save_state(), Ok(None));
+        assert_eq!(load_state(Some("{}")), Ok(()));
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let scores = Rc::new(RefCell::new(HashMap::from([("alice".to_string(), 3.5)])));
+        let (saved, loaded) = (scores.clone(), scores.clone());
+        persist("scores", move || saved.borrow().clone(), move |x: Option<HashMap<String, f64>>| *loaded.borrow_mut() = x.unwrap_or_default());
+        let count = Rc::new(Cell::new(7u32));
+        let (saved, loaded) = (count.clone(), count.clone());
+        persist("count", move || saved.get(), move |x: Option<u32>| loaded.set(x.unwrap_or_default()));
+
+        let state = save_state().unwrap().unwrap();
+        scores.borrow_mut().clear();
+        count.set(0);
+        load_state(Some(&state)).unwrap();
+        assert_eq!(*scores.borrow(), HashMap::from([("alice".to_string(), 3.5)]));
+        assert_eq!(count.get(), 7);
+
+        // Projects without state reset it
+        load_state(None).unwrap();
+        assert!(scores.borrow().is_empty());
+        assert_eq!(count.get(), 0);
+    }
+
+    #[test]
+    fn invalid_state_is_reported() {
+        let count = Rc::new(Cell::new(1u32));
+        let loaded = count.clone();
+        persist("count", || 1u32, move |x: Option<u32>| loaded.set(x.unwrap_or_default()));
+        persist("other", || 2u32, |_: Option<u32>| ());
+
+        assert!(load_state(Some("not json")).unwrap_err().starts_with("invalid extension state: "));
+        assert_eq!(count.get(), 1);
+        let e = load_state(Some(r#"{"count": "many", "other": -1}"#)).unwrap_err();
+        assert!(e.starts_with("could not load count: invalid type: string \"many\"") && e.contains("\ncould not load other: "), "{e}");
+    }
+}
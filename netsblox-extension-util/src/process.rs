@@ -0,0 +1,134 @@
+use std::fmt;
+#[cfg(feature = "testing")]
+use std::rc::Rc;
+
+use js_sys::{Promise, Reflect};
+use wasm_bindgen::{convert::FromWasmAbi, describe::WasmDescribe, prelude::*};
+use wasm_bindgen_futures::JsFuture;
+
+#[cfg(feature = "testing")]
+use crate::testing::MockProcess;
+use crate::{sprite::{is_sprite, stage_of}, Sprite, Stage};
+
+#[wasm_bindgen(inline_js = r#"
+export function waitForStep(process) {
+    return new Promise(resolve => {
+        if (process.rustAwaiting) {
+            // Resolved by the generated extension's await helper the next time the process steps
+            (process.rustYields ??= []).push(resolve);
+        } else {
+            // Stopped or timed out, so nothing steps for this block anymore
+            setTimeout(resolve);
+        }
+    });
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = waitForStep)]
+    fn wait_for_step(process: &JsValue) -> Promise;
+}
+
+// Milliseconds since the epoch, measured natively when mocks run in tests
+fn now() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    return js_sys::Date::now();
+    #[cfg(not(target_arch = "wasm32"))]
+    return std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0.0, |x| x.as_secs_f64() * 1000.0);
+}
+
+/// The process running a block, passed in for blocks declared with `pass_proc = true`
+pub struct Process {
+    pub(crate) process: JsValue,
+    #[cfg(feature = "testing")]
+    pub(crate) mock: Option<Rc<MockProcess>>,
+}
+
+impl Clone for Process {
+    fn clone(&self) -> Self {
+        mocked!(self, mock => Process::mock(mock.clone()));
+        Process::from_js(self.process.clone())
+    }
+}
+
+impl fmt::Debug for Process {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        mocked!(self, mock => f.debug_tuple("Process").field(&mock.receiver_id()).finish());
+        f.debug_tuple("Process").field(&self.process).finish()
+    }
+}
+
+impl Process {
+    pub fn from_js(process: JsValue) -> Self {
+        Self {
+            process,
+            #[cfg(feature = "testing")]
+            mock: None,
+        }
+    }
+
+    /// The sprite running the block, or `None` if it is run by the stage
+    pub fn sprite(&self) -> Result<Option<Sprite>, JsValue> {
+        mocked!(self, mock => Ok(mock.sprite.clone().map(Sprite::mock)));
+        let receiver = Reflect::get(&self.process, &"receiver".into())?;
+        Ok(is_sprite(&receiver).then(|| Sprite::from_morph(receiver)))
+    }
+
+    pub fn stage(&self) -> Result<Stage, JsValue> {
+        mocked!(self, mock => Ok(Stage::mock(mock.stage.clone())));
+        let receiver = Reflect::get(&self.process, &"receiver".into())?;
+        Ok(Stage::from_morph(stage_of(&receiver)?))
+    }
+
+    /// Wait for the process's next step, letting NetsBlox run other scripts and redraw in between.
+    ///
+    /// Only async blocks can yield. If the process is stopped meanwhile, pending yields complete once more so blocks taking a
+    /// [`CancellationToken`](crate::CancellationToken) can notice. Later yields complete without waiting for NetsBlox, so a block
+    /// that keeps going anyway runs to its end in the background and is dropped rather than staying suspended.
+    pub async fn yield_now(&self) {
+        mocked!(self, mock => mock.yields.set(mock.yields.get() + 1));
+        let _ = JsFuture::from(wait_for_step(&self.process)).await;
+    }
+
+    /// Create a [`Yielder`] for long loops, which yields once `slice_ms` milliseconds have passed since it last yielded
+    pub fn yielder(&self, slice_ms: f64) -> Yielder {
+        Yielder { process: self.clone(), slice_ms, last: now() }
+    }
+
+    /// The underlying NetsBlox Process
+    pub fn as_js(&self) -> &JsValue {
+        &self.process
+    }
+}
+
+impl WasmDescribe for Process {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl FromWasmAbi for Process {
+    type Abi = <JsValue as FromWasmAbi>::Abi;
+
+    unsafe fn from_abi(js: Self::Abi) -> Self {
+        Process::from_js(JsValue::from_abi(js))
+    }
+}
+
+/// Yields to NetsBlox's scheduler at most once per time slice, see [`Process::yielder`].
+///
+/// Calling [`Yielder::tick`] on every loop iteration keeps the IDE responsive without waiting a frame each iteration.
+#[derive(Debug, Clone)]
+pub struct Yielder {
+    process: Process,
+    slice_ms: f64,
+    last: f64,
+}
+
+impl Yielder {
+    pub async fn tick(&mut self) {
+        if now() - self.last >= self.slice_ms {
+            self.process.yield_now().await;
+            self.last = now();
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use js_sys::{Array, Function, Promise, Reflect};
+use wasm_bindgen::{convert::{FromWasmAbi, IntoWasmAbi}, describe::WasmDescribe, prelude::*};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::handle::{finalization_registry, register_finalizer};
+
+#[wasm_bindgen(inline_js = r#"
+export function startRing(process, context, args) {
+    return new Promise((resolve, reject) => {
+        if (!context) {
+            return resolve(undefined); // Empty C-slot
+        }
+        const child = new Process(null, process.receiver);
+        child.initializeFor(context, new List(args));
+        // Stepped by the calling process while its async block waits, see the generated extension's await helper
+        (process.rustRings ??= []).push({ process: child, resolve, reject });
+    });
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = startRing)]
+    fn start_ring(process: &JsValue, context: &JsValue, args: Array) -> Promise;
+}
+
+#[wasm_bindgen(inline_js = r#"
+export function makeRustRing(id, dispatch) {
+    // NetsBlox's call and run blocks accept JS functions as rings, passing the calling process last
+    const ring = function (...args) {
+        args.pop();
+        return dispatch(id, args);
+    };
+    ring.toString = () => `<Rust function #${id}>`;
+    return ring;
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = makeRustRing)]
+    fn make_rust_ring(id: u32, dispatch: &Function) -> Function;
+}
+
+/// A reporter, command or predicate ring passed to a block through a `%repRing`, `%cmdRing` or `%predRing` slot,
+/// or the script enclosed by a C-slot (`%c`, `%cs` or `%ca`).
+///
+/// Calling the ring runs it as part of the process that called the block, so the block must be an `async fn`.
+#[derive(Debug, Clone)]
+pub struct Ring {
+    context: JsValue,
+    process: JsValue,
+}
+
+impl Ring {
+    /// Create a ring from a NetsBlox Context and the Process that will run it
+    pub fn new(process: JsValue, context: JsValue) -> Self {
+        Self { context, process }
+    }
+
+    /// Recover a ring from the value the generated extension passes for ring slots
+    pub fn from_js(value: &JsValue) -> Result<Self, JsValue> {
+        let context = Reflect::get(value, &"ring".into())?;
+        let process = Reflect::get(value, &"process".into())?;
+        if process.is_undefined() {
+            return Err("expected a ring".into());
+        }
+        Ok(Self { context, process })
+    }
+
+    /// Run the ring with the given inputs, resolving to what it reports (undefined for commands)
+    pub async fn call(&self, args: &[JsValue]) -> Result<JsValue, JsValue> {
+        JsFuture::from(start_ring(&self.process, &self.context, args.iter().collect())).await
+    }
+
+    /// The underlying NetsBlox Context
+    pub fn context(&self) -> &JsValue {
+        &self.context
+    }
+}
+
+impl WasmDescribe for Ring {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl FromWasmAbi for Ring {
+    type Abi = <JsValue as FromWasmAbi>::Abi;
+
+    unsafe fn from_abi(js: Self::Abi) -> Self {
+        Ring::from_js(&JsValue::from_abi(js)).unwrap_or_else(|e| wasm_bindgen::throw_val(e))
+    }
+}
+
+type RustRingFn = Rc<dyn Fn(Vec<JsValue>) -> Result<JsValue, JsValue>>;
+
+#[derive(Default)]
+struct RustRingStore {
+    next_id: u32,
+    fns: HashMap<u32, RustRingFn>,
+    dispatch: Option<Function>,
+    registry: Option<JsValue>,
+}
+
+thread_local! {
+    static RUST_RINGS: RefCell<RustRingStore> = RefCell::new(RustRingStore::default());
+}
+
+/// A Rust closure that reporters can return as a ring, which students run with NetsBlox's call and run blocks.
+///
+/// The closure is dropped once NetsBlox no longer references the ring, or when a new role is opened.
+pub struct RustRing {
+    f: RustRingFn,
+}
+
+impl fmt::Debug for RustRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RustRing")
+    }
+}
+
+impl RustRing {
+    pub fn new(f: impl Fn(Vec<JsValue>) -> JsValue + 'static) -> Self {
+        Self { f: Rc::new(move |args| Ok(f(args))) }
+    }
+
+    /// Create a ring whose errors are reported by the block that called it
+    pub fn fallible(f: impl Fn(Vec<JsValue>) -> Result<JsValue, JsValue> + 'static) -> Self {
+        Self { f: Rc::new(f) }
+    }
+
+    /// Create the JS function NetsBlox stores for this ring
+    pub fn to_js(self) -> JsValue {
+        RUST_RINGS.with_borrow_mut(|store| {
+            store.next_id += 1;
+            let id = store.next_id;
+            store.fns.insert(id, self.f);
+
+            let dispatch = store.dispatch.get_or_insert_with(|| {
+                let dispatch = Closure::<dyn Fn(u32, Vec<JsValue>) -> Result<JsValue, JsValue>>::new(call_rust_ring);
+                dispatch.into_js_value().unchecked_into()
+            });
+            let ring = make_rust_ring(id, dispatch);
+
+            let registry = store.registry.get_or_insert_with(|| finalization_registry(release_rust_ring));
+            register_finalizer(registry, &ring, id);
+
+            ring.into()
+        })
+    }
+}
+
+fn call_rust_ring(id: u32, args: Vec<JsValue>) -> Result<JsValue, JsValue> {
+    // Cloned out of the store so the closure can create further rings
+    let f = RUST_RINGS.with_borrow(|store| store.fns.get(&id).cloned()).ok_or_else(|| JsValue::from_str(&format!("Rust function #{id} no longer exists")))?;
+    f(args)
+}
+
+fn release_rust_ring(id: u32) {
+    let f = RUST_RINGS.with_borrow_mut(|store| store.fns.remove(&id));
+    drop(f);
+}
+
+/// Drop every closure returned as a ring
+pub fn clear_rust_rings() {
+    let fns = RUST_RINGS.with_borrow_mut(|store| std::mem::take(&mut store.fns));
+    drop(fns);
+}
+
+impl From<RustRing> for JsValue {
+    fn from(ring: RustRing) -> Self {
+        ring.to_js()
+    }
+}
+
+impl WasmDescribe for RustRing {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl IntoWasmAbi for RustRing {
+    type Abi = <JsValue as IntoWasmAbi>::Abi;
+
+    fn into_abi(self) -> Self::Abi {
+        self.to_js().into_abi()
+    }
+}
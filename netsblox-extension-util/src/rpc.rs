@@ -0,0 +1,195 @@
+use std::{cell::RefCell, collections::HashMap, fmt::{self, Write}, rc::Rc};
+
+use js_sys::{Promise, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+#[wasm_bindgen(inline_js = r#"
+export async function callRpc(path, args) {
+    const ide = world.children[0];
+    const host = ide.services?.defaultHost?.url ?? SERVICES_URL;
+    // Same client id and cookies as the IDE's own RPC blocks, so services see this project's session
+    const url = `${host}/${path}?clientId=${encodeURIComponent(ide.cloud.clientId)}&t=${Date.now()}`;
+    const res = await fetch(url, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: args,
+        credentials: 'include',
+    });
+    const mimeType = res.headers.get('Content-Type') ?? '';
+    if (res.ok && mimeType.startsWith('image/')) {
+        return { status: res.status, mimeType, data: new Uint8Array(await res.arrayBuffer()) };
+    }
+    return { status: res.status, mimeType, text: await res.text() };
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = callRpc)]
+    fn call_rpc(path: &str, args: &str) -> Promise;
+}
+
+/// A decoded RPC result
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcValue {
+    Text(String),
+    Json(serde_json::Value),
+    Image { mime_type: String, data: Vec<u8> },
+}
+
+impl RpcValue {
+    /// The result as JSON, with text results parsed if possible
+    pub fn json(&self) -> Option<serde_json::Value> {
+        match self {
+            RpcValue::Json(x) => Some(x.clone()),
+            RpcValue::Text(x) => serde_json::from_str(x).ok(),
+            RpcValue::Image { .. } => None,
+        }
+    }
+}
+
+/// Error returned when an RPC fails
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcError {
+    /// The request could not be sent, e.g. because the IDE is offline
+    Network(String),
+    /// The service reported an error
+    Service { status: u16, message: String },
+    /// The response could not be decoded
+    Decode(String),
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Network(e) => write!(f, "could not reach service: {e}"),
+            RpcError::Service { status, message } => write!(f, "service error ({status}): {message}"),
+            RpcError::Decode(e) => write!(f, "invalid service response: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<RpcError> for JsValue {
+    fn from(e: RpcError) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+/// A stand-in for a NetsBlox service, see [`set_mock_service`]
+pub trait RpcService {
+    fn call(&self, method: &str, args: &serde_json::Value) -> Result<RpcValue, RpcError>;
+}
+
+impl<F: Fn(&str, &serde_json::Value) -> Result<RpcValue, RpcError>> RpcService for F {
+    fn call(&self, method: &str, args: &serde_json::Value) -> Result<RpcValue, RpcError> {
+        self(method, args)
+    }
+}
+
+thread_local! {
+    static MOCK_SERVICES: RefCell<HashMap<String, Rc<dyn RpcService>>> = RefCell::new(HashMap::new());
+}
+
+/// Answer every RPC to the named service with `service` instead of the cloud, for testing offline
+pub fn set_mock_service(name: &str, service: impl RpcService + 'static) {
+    MOCK_SERVICES.with_borrow_mut(|x| x.insert(name.to_string(), Rc::new(service)));
+}
+
+/// Remove every service added with [`set_mock_service`]
+pub fn clear_mock_services() {
+    MOCK_SERVICES.with_borrow_mut(|x| x.clear());
+}
+
+fn js_error(e: JsValue) -> String {
+    e.as_string().or_else(|| Reflect::get(&e, &"message".into()).ok()?.as_string()).unwrap_or_else(|| format!("{e:?}"))
+}
+
+/// Call a method of a NetsBlox service, e.g. `rpc("Weather", "temperature", &json!({ "latitude": 36.2, "longitude": -86.8 })).await`.
+///
+/// Arguments are given as a JSON object keyed by argument name.
+pub async fn rpc(service: &str, method: &str, args: &serde_json::Value) -> Result<RpcValue, RpcError> {
+    if let Some(mock) = MOCK_SERVICES.with_borrow(|x| x.get(service).cloned()) {
+        return mock.call(method, args);
+    }
+
+    let path = format!("{}/{}", encode_segment(service), encode_segment(method));
+    let res = JsFuture::from(call_rpc(&path, &args.to_string())).await.map_err(|e| RpcError::Network(js_error(e)))?;
+    let get = |key: &str| Reflect::get(&res, &key.into()).map_err(|e| RpcError::Decode(js_error(e)));
+
+    let status = get("status")?.as_f64().unwrap_or_default() as u16;
+    let mime_type = get("mimeType")?.as_string().unwrap_or_default();
+    let data = get("data")?;
+    if (200..300).contains(&status) && !data.is_undefined() {
+        return Ok(RpcValue::Image { mime_type, data: Uint8Array::new(&data).to_vec() });
+    }
+    decode_text(status, &mime_type, get("text")?.as_string().unwrap_or_default())
+}
+
+// Escape a service or method name for the URL path like `encodeURIComponent`, so `/`, `?` or `#` can't change the request
+fn encode_segment(name: &str) -> String {
+    let mut res = String::new();
+    for b in name.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'!' | b'~' | b'*' | b'\'' | b'(' | b')' => res.push(b as char),
+            _ => write!(res, "%{b:02X}").unwrap(),
+        }
+    }
+    res
+}
+
+// Errors are always sent as text, whatever the method returns on success
+fn decode_text(status: u16, mime_type: &str, text: String) -> Result<RpcValue, RpcError> {
+    if !(200..300).contains(&status) {
+        return Err(RpcError::Service { status, message: text });
+    }
+    if mime_type.starts_with("application/json") {
+        return serde_json::from_str(&text).map(RpcValue::Json).map_err(|e| RpcError::Decode(e.to_string()));
+    }
+    Ok(RpcValue::Text(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::Future, pin::pin, task::{Context, Poll, Waker}};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn now<T>(f: impl Future<Output = T>) -> T {
+        match pin!(f).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(x) => x,
+            Poll::Pending => panic!("rpc did not finish immediately"),
+        }
+    }
+
+    #[test]
+    fn mock_services_answer_rpcs() {
+        set_mock_service("Math", |method: &str, args: &serde_json::Value| match method {
+            "add" => Ok(RpcValue::Json(json!(args["a"].as_f64().unwrap() + args["b"].as_f64().unwrap()))),
+            _ => Err(RpcError::Service { status: 404, message: format!("no method {method}") }),
+        });
+
+        assert_eq!(now(rpc("Math", "add", &json!({ "a": 1, "b": 2.5 }))), Ok(RpcValue::Json(json!(3.5))));
+        assert_eq!(now(rpc("Math", "sub", &json!({}))), Err(RpcError::Service { status: 404, message: "no method sub".into() }));
+
+        clear_mock_services();
+        assert!(MOCK_SERVICES.with_borrow(|x| x.is_empty()));
+    }
+
+    #[test]
+    fn names_are_escaped_in_paths() {
+        assert_eq!(encode_segment("Weather"), "Weather");
+        assert_eq!(encode_segment("a/b?c#d e"), "a%2Fb%3Fc%23d%20e");
+        assert_eq!(encode_segment("café"), "caf%C3%A9");
+    }
+
+    #[test]
+    fn responses_are_decoded_by_status_and_type() {
+        assert_eq!(decode_text(200, "application/json; charset=utf-8", "[1, 2]".into()), Ok(RpcValue::Json(json!([1, 2]))));
+        assert_eq!(decode_text(200, "text/plain", "[1, 2]".into()).unwrap().json(), Some(json!([1, 2])));
+        assert_eq!(decode_text(500, "application/json", "Service is down".into()), Err(RpcError::Service { status: 500, message: "Service is down".into() }));
+        assert!(matches!(decode_text(200, "application/json", "{".into()), Err(RpcError::Decode(_))));
+    }
+}
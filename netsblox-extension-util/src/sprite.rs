@@ -0,0 +1,239 @@
+use std::fmt;
+#[cfg(feature = "testing")]
+use std::rc::Rc;
+
+use js_sys::{Array, Function, Reflect};
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "testing")]
+use crate::testing::{Bubble, MockSprite, MockStageState};
+
+#[wasm_bindgen(inline_js = r#"
+export function stageOf(receiver) {
+    return receiver instanceof StageMorph ? receiver : receiver.parentThatIsA(StageMorph);
+}
+
+export function isSprite(value) {
+    return value instanceof SpriteMorph;
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = stageOf, catch)]
+    pub(crate) fn stage_of(receiver: &JsValue) -> Result<JsValue, JsValue>;
+    #[wasm_bindgen(js_name = isSprite)]
+    pub(crate) fn is_sprite(value: &JsValue) -> bool;
+}
+
+// Call a method of a NetsBlox morph by name
+fn call(obj: &JsValue, method: &str, args: &[JsValue]) -> Result<JsValue, JsValue> {
+    let f = Reflect::get(obj, &method.into())?.dyn_into::<Function>().map_err(|_| JsValue::from_str(&format!("{method} is not available")))?;
+    Reflect::apply(&f, obj, &args.iter().collect::<Array>())
+}
+
+fn number(value: JsValue) -> f64 {
+    value.as_f64().or_else(|| value.as_string()?.parse().ok()).unwrap_or_default()
+}
+
+/// A sprite on the stage. Positions are in stage coordinates, with (0, 0) at the center.
+pub struct Sprite {
+    pub(crate) morph: JsValue,
+    #[cfg(feature = "testing")]
+    pub(crate) mock: Option<Rc<MockSprite>>,
+}
+
+// Not derived, since cloning or printing a JsValue needs JS, which mocks run without
+impl Clone for Sprite {
+    fn clone(&self) -> Self {
+        mocked!(self, mock => Sprite::mock(mock.clone()));
+        Sprite::from_morph(self.morph.clone())
+    }
+}
+
+impl fmt::Debug for Sprite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        mocked!(self, mock => f.debug_tuple("Sprite").field(&mock.state.borrow()).finish());
+        f.debug_tuple("Sprite").field(&self.morph).finish()
+    }
+}
+
+impl Sprite {
+    pub(crate) fn from_morph(morph: JsValue) -> Self {
+        Self {
+            morph,
+            #[cfg(feature = "testing")]
+            mock: None,
+        }
+    }
+
+    pub fn name(&self) -> Result<String, JsValue> {
+        mocked!(self, mock => Ok(mock.state.borrow().name.clone()));
+        Ok(Reflect::get(&self.morph, &"name".into())?.as_string().unwrap_or_default())
+    }
+
+    pub fn position(&self) -> Result<(f64, f64), JsValue> {
+        mocked!(self, mock => Ok(mock.state.borrow().position));
+        Ok((number(call(&self.morph, "xPosition", &[])?), number(call(&self.morph, "yPosition", &[])?)))
+    }
+
+    pub fn set_position(&self, x: f64, y: f64) -> Result<(), JsValue> {
+        mocked!(self, mock => {
+            mock.state.borrow_mut().position = (x, y);
+            Ok(())
+        });
+        call(&self.morph, "gotoXY", &[x.into(), y.into()])?;
+        Ok(())
+    }
+
+    /// Direction in degrees, with 90 pointing right
+    pub fn heading(&self) -> Result<f64, JsValue> {
+        mocked!(self, mock => Ok(mock.state.borrow().heading));
+        Ok(number(call(&self.morph, "direction", &[])?))
+    }
+
+    pub fn set_heading(&self, degrees: f64) -> Result<(), JsValue> {
+        mocked!(self, mock => {
+            // NetsBlox reports directions from -180 to 180
+            mock.state.borrow_mut().heading = 180.0 - (180.0 - degrees).rem_euclid(360.0);
+            Ok(())
+        });
+        call(&self.morph, "setHeading", &[degrees.into()])?;
+        Ok(())
+    }
+
+    /// Size as a percentage of the costume's size
+    pub fn size(&self) -> Result<f64, JsValue> {
+        mocked!(self, mock => Ok(mock.state.borrow().size));
+        Ok(number(call(&self.morph, "getScale", &[])?))
+    }
+
+    pub fn set_size(&self, percent: f64) -> Result<(), JsValue> {
+        mocked!(self, mock => {
+            mock.state.borrow_mut().size = percent;
+            Ok(())
+        });
+        call(&self.morph, "setScale", &[percent.into()])?;
+        Ok(())
+    }
+
+    /// Index of the current costume, starting at 1, or 0 when wearing no costume
+    pub fn costume_index(&self) -> Result<usize, JsValue> {
+        mocked!(self, mock => Ok(mock.state.borrow().costume_index));
+        Ok(number(call(&self.morph, "getCostumeIdx", &[])?) as usize)
+    }
+
+    /// Switch to a costume by name, by index starting at 1, or to a costume value
+    pub fn switch_costume(&self, costume: &JsValue) -> Result<(), JsValue> {
+        #[cfg(feature = "testing")]
+        assert!(self.mock.is_none(), "mock sprites cannot switch costumes by value, use testing::update_sprite instead");
+        call(&self.morph, "doSwitchToCostume", std::slice::from_ref(costume))?;
+        Ok(())
+    }
+
+    /// Show a speech bubble, or remove it if the text is empty
+    pub fn say(&self, text: &str) -> Result<(), JsValue> {
+        self.bubble(text, false)
+    }
+
+    /// Show a thought bubble, or remove it if the text is empty
+    pub fn think(&self, text: &str) -> Result<(), JsValue> {
+        self.bubble(text, true)
+    }
+
+    fn bubble(&self, text: &str, thinking: bool) -> Result<(), JsValue> {
+        mocked!(self, mock => {
+            mock.state.borrow_mut().bubble = (!text.is_empty()).then(|| Bubble { text: text.to_string(), thinking });
+            Ok(())
+        });
+        match text {
+            "" => call(&self.morph, "stopTalking", &[])?,
+            _ => call(&self.morph, "bubble", &[text.into(), thinking.into()])?,
+        };
+        Ok(())
+    }
+
+    pub fn is_visible(&self) -> Result<bool, JsValue> {
+        mocked!(self, mock => Ok(mock.state.borrow().visible));
+        Ok(Reflect::get(&self.morph, &"isVisible".into())?.is_truthy())
+    }
+
+    pub fn set_visible(&self, visible: bool) -> Result<(), JsValue> {
+        mocked!(self, mock => {
+            mock.state.borrow_mut().visible = visible;
+            Ok(())
+        });
+        call(&self.morph, if visible { "show" } else { "hide" }, &[])?;
+        Ok(())
+    }
+
+    /// The underlying NetsBlox SpriteMorph
+    pub fn as_js(&self) -> &JsValue {
+        &self.morph
+    }
+}
+
+/// The stage of the project running a block
+pub struct Stage {
+    pub(crate) morph: JsValue,
+    #[cfg(feature = "testing")]
+    pub(crate) mock: Option<Rc<MockStageState>>,
+}
+
+impl Clone for Stage {
+    fn clone(&self) -> Self {
+        mocked!(self, mock => Stage::mock(mock.clone()));
+        Stage::from_morph(self.morph.clone())
+    }
+}
+
+impl fmt::Debug for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        mocked!(self, mock => f.debug_tuple("Stage").field(&mock.dimensions.get()).finish());
+        f.debug_tuple("Stage").field(&self.morph).finish()
+    }
+}
+
+impl Stage {
+    pub(crate) fn from_morph(morph: JsValue) -> Self {
+        Self {
+            morph,
+            #[cfg(feature = "testing")]
+            mock: None,
+        }
+    }
+
+    /// Width and height in stage coordinates
+    pub fn dimensions(&self) -> Result<(f64, f64), JsValue> {
+        mocked!(self, mock => Ok(mock.dimensions.get()));
+        let dimensions = Reflect::get(&self.morph, &"dimensions".into())?;
+        Ok((number(Reflect::get(&dimensions, &"x".into())?), number(Reflect::get(&dimensions, &"y".into())?)))
+    }
+
+    /// Mouse position in stage coordinates
+    pub fn mouse_position(&self) -> Result<(f64, f64), JsValue> {
+        mocked!(self, mock => Ok(mock.mouse_position.get()));
+        Ok((number(call(&self.morph, "reportMouseX", &[])?), number(call(&self.morph, "reportMouseY", &[])?)))
+    }
+
+    /// Every sprite on the stage, including clones
+    pub fn sprites(&self) -> Result<Vec<Sprite>, JsValue> {
+        mocked!(self, mock => Ok(mock.sprites.borrow().iter().cloned().map(Sprite::mock).collect()));
+        let children: Array = Reflect::get(&self.morph, &"children".into())?.dyn_into()?;
+        Ok(children.iter().filter(is_sprite).map(Sprite::from_morph).collect())
+    }
+
+    /// Find a sprite by name, ignoring clones
+    pub fn sprite(&self, name: &str) -> Result<Option<Sprite>, JsValue> {
+        mocked!(self, mock => Ok(mock.sprites.borrow().iter().find(|x| x.state.borrow().name == name && !x.state.borrow().is_clone).cloned().map(Sprite::mock)));
+        for sprite in self.sprites()? {
+            if sprite.name()? == name && !Reflect::get(&sprite.morph, &"isTemporary".into())?.is_truthy() {
+                return Ok(Some(sprite));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The underlying NetsBlox StageMorph
+    pub fn as_js(&self) -> &JsValue {
+        &self.morph
+    }
+}
@@ -0,0 +1,175 @@
+use std::{any::{Any, TypeId}, cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{handle::short_type_name, Process, Sprite, Stage};
+
+#[wasm_bindgen(inline_js = r#"
+// Kept in a WeakMap rather than on the morph, since clones copy their parent's properties
+const ids = new WeakMap();
+let nextId = 0;
+let registry = null;
+
+export function stateId(morph, release) {
+    let id = ids.get(morph);
+    if (id !== undefined) {
+        return id;
+    }
+    id = ++nextId;
+    ids.set(morph, id);
+
+    if (!registry) {
+        registry = typeof FinalizationRegistry === 'undefined' ? null : new FinalizationRegistry(release);
+        // Removed clones and deleted sprites are destroyed, so their state goes right away
+        const destroy = SpriteMorph.prototype.destroy;
+        SpriteMorph.prototype.destroy = function () {
+            const id = ids.get(this);
+            if (id !== undefined) {
+                ids.delete(this);
+                release(id);
+            }
+            return destroy.apply(this, arguments);
+        };
+    }
+    registry?.register(morph, id);
+    return id;
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = stateId)]
+    fn state_id(morph: &JsValue, release: &Closure<dyn Fn(u32)>) -> u32;
+}
+
+/// Error returned when state is accessed while it is already in use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// `with_state` was called again for the same sprite and type from inside its closure
+    InUse { type_name: &'static str },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::InUse { type_name } => write!(f, "{type_name} state is already in use by this sprite"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<StateError> for JsValue {
+    fn from(e: StateError) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+#[derive(Default)]
+struct StateStore {
+    values: HashMap<(u32, TypeId), Rc<dyn Any>>,
+    release: Option<Closure<dyn Fn(u32)>>,
+}
+
+thread_local! {
+    static STATE: RefCell<StateStore> = RefCell::new(StateStore::default());
+}
+
+pub(crate) fn release_state(id: u32) {
+    let values = STATE.with_borrow_mut(|store| {
+        let keys = store.values.keys().filter(|(x, _)| *x == id).copied().collect::<Vec<_>>();
+        keys.into_iter().filter_map(|key| store.values.remove(&key)).collect::<Vec<_>>()
+    });
+    drop(values); // Dropped outside the borrow in case the state holds handles or rings
+}
+
+fn morph_id(morph: &JsValue) -> u32 {
+    STATE.with_borrow_mut(|store| {
+        let release = store.release.get_or_insert_with(|| Closure::new(release_state));
+        state_id(morph, release)
+    })
+}
+
+fn with_state_id<T: Default + 'static, R>(id: u32, f: impl FnOnce(&mut T) -> R) -> Result<R, StateError> {
+    let cell = STATE.with_borrow_mut(|store| {
+        let value = store.values.entry((id, TypeId::of::<T>())).or_insert_with(|| Rc::new(RefCell::new(T::default())));
+        value.clone().downcast::<RefCell<T>>().unwrap()
+    });
+    // Borrowed outside the store so `f` can use the state of other sprites
+    let mut value = cell.try_borrow_mut().map_err(|_| StateError::InUse { type_name: short_type_name::<T>() })?;
+    Ok(f(&mut value))
+}
+
+/// Drop the state of every sprite and the stage
+pub fn clear_sprite_state() {
+    let values = STATE.with_borrow_mut(|store| std::mem::take(&mut store.values));
+    drop(values);
+}
+
+impl Sprite {
+    /// Access this sprite's value of type `T`, starting from `T::default()`.
+    ///
+    /// Clones start with their own state, and state is dropped when the sprite or clone is removed or a role is opened.
+    /// Fails if `f` accesses the same sprite's value of type `T` again.
+    pub fn with_state<T: Default + 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, StateError> {
+        mocked!(self, mock => with_state_id(mock.id, f));
+        with_state_id(morph_id(&self.morph), f)
+    }
+
+    /// Drop every value stored for this sprite
+    pub fn clear_state(&self) {
+        mocked!(self, mock => release_state(mock.id));
+        release_state(morph_id(&self.morph));
+    }
+}
+
+impl Stage {
+    /// Access the stage's value of type `T`, starting from `T::default()`, see [`Sprite::with_state`]
+    pub fn with_state<T: Default + 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, StateError> {
+        mocked!(self, mock => with_state_id(mock.id, f));
+        with_state_id(morph_id(&self.morph), f)
+    }
+
+    /// Drop every value stored for the stage
+    pub fn clear_state(&self) {
+        mocked!(self, mock => release_state(mock.id));
+        release_state(morph_id(&self.morph));
+    }
+}
+
+impl Process {
+    /// Access the value of type `T` stored for the sprite or stage running the block, see [`Sprite::with_state`]
+    pub fn with_state<T: Default + 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, JsValue> {
+        mocked!(self, mock => Ok(with_state_id(mock.receiver_id(), f)?));
+        let receiver = js_sys::Reflect::get(self.as_js(), &"receiver".into())?;
+        Ok(with_state_id(morph_id(&receiver), f)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_is_kept_per_id_and_type() {
+        with_state_id(1, |x: &mut f64| *x += 2.0).unwrap();
+        with_state_id(1, |x: &mut String| x.push('a')).unwrap();
+        assert_eq!(with_state_id(1, |x: &mut f64| *x), Ok(2.0));
+        assert_eq!(with_state_id(2, |x: &mut f64| *x), Ok(0.0));
+
+        release_state(1);
+        assert_eq!(with_state_id(1, |x: &mut String| x.clone()), Ok(String::new()));
+        assert_eq!(with_state_id(2, |x: &mut f64| *x), Ok(0.0));
+    }
+
+    #[test]
+    fn nested_access_to_the_same_state_fails() {
+        let res = with_state_id(1, |outer: &mut Vec<u32>| {
+            outer.push(1);
+            // Other sprites and other types are fine
+            with_state_id(2, |x: &mut Vec<u32>| x.push(2)).unwrap();
+            with_state_id(1, |x: &mut f64| *x = 1.0).unwrap();
+            with_state_id(1, |x: &mut Vec<u32>| x.push(3))
+        });
+        assert_eq!(res, Ok(Err(StateError::InUse { type_name: "Vec<u32>" })));
+        assert_eq!(with_state_id(1, |x: &mut Vec<u32>| x.clone()), Ok(vec![1]));
+    }
+}
@@ -3,13 +3,312 @@
  */
 
 (function () {    
+$HELPERS
+    // Translations from the locales directory, keyed by NetsBlox language code
+    const $NO_SPACE_EXTENSION_NAME_locales = {
+$LOCALES
+    };
+    function $NO_SPACE_EXTENSION_NAME_translate(kind, key, fallback) {
+        const locale = $NO_SPACE_EXTENSION_NAME_locales[SnapTranslator.language];
+        const res = locale && locale[kind][key];
+        return res === undefined ? fallback : res;
+    }
+    // Translated specs may order slots differently, so inputs are mapped back to the original order before calling the block
+    function $NO_SPACE_EXTENSION_NAME_localizeBlock(name, spec, defaults, fn) {
+        const [trSpec, order] = $NO_SPACE_EXTENSION_NAME_translate('blocks', name, [spec, null]);
+        if (!order) {
+            return [trSpec, defaults, fn];
+        }
+        return [trSpec, order.map(i => defaults[i]), function (...args) {
+            const inputs = [];
+            order.forEach((j, i) => inputs[j] = args[i]);
+            return fn.apply(this, inputs);
+        }];
+    }
+    if (Object.keys($NO_SPACE_EXTENSION_NAME_locales).length > 0) {
+        const translate = SnapTranslator.translate;
+        SnapTranslator.translate = function (string) {
+            return $NO_SPACE_EXTENSION_NAME_translate('categories', string, undefined) ?? translate.apply(this, arguments);
+        };
+    }
+
+    // Cancellation tokens are created for every async block call and cancelled when it times out or its process is stopped
+    function $NO_SPACE_EXTENSION_NAME_token() {
+        let resolve;
+        const token = { cancelled: false, reason: null, promise: new Promise(r => resolve = r) };
+        token.cancel = reason => {
+            if (!token.cancelled) {
+                Object.assign(token, { cancelled: true, reason });
+                resolve(reason);
+            }
+        };
+        return token;
+    }
+
+    function $NO_SPACE_EXTENSION_NAME_cancel(proc, reason) {
+        for (const token of proc.rustTokens ?? []) {
+            token.cancel(reason);
+        }
+        proc.rustTokens?.clear();
+        // Released once more so blocks checking their token can stop. No block is awaited anymore, so later yields
+        // complete on the next task, letting blocks that ignore their token run to the end and be dropped
+        proc.rustAwaiting = null;
+        const yields = proc.rustYields ?? [];
+        proc.rustYields = [];
+        yields.forEach(resolve => resolve());
+        for (const call of proc.rustRings ?? []) {
+            call.process.stop();
+            call.reject(new Error('ring was cancelled'));
+        }
+        proc.rustRings = [];
+    }
+
+    const $NO_SPACE_EXTENSION_NAME_stop = Process.prototype.stop;
+    Process.prototype.stop = function () {
+        $NO_SPACE_EXTENSION_NAME_cancel(this, 'stopped');
+        return $NO_SPACE_EXTENSION_NAME_stop.apply(this, arguments);
+    };
+
+    // Async blocks are re-evaluated every frame until their promise settles, stepping any rings they called meanwhile
+    function $NO_SPACE_EXTENSION_NAME_await(proc, start, timeoutMs) {
+        const context = proc.context;
+        if (!context.rustAsync) {
+            const token = $NO_SPACE_EXTENSION_NAME_token();
+            const state = context.rustAsync = { done: false, timedOut: false, token };
+            (proc.rustTokens ??= new Set()).add(token);
+            proc.rustAwaiting = state;
+            // A timer rather than a check each frame, so the block is cancelled on time even if frames are slow or paused
+            const timer = timeoutMs === undefined ? null : setTimeout(() => {
+                state.timedOut = true;
+                $NO_SPACE_EXTENSION_NAME_cancel(proc, 'timeout');
+            }, timeoutMs);
+            start(token).then(value => Object.assign(state, { done: true, value }), error => Object.assign(state, { done: true, error }))
+                .finally(() => {
+                    clearTimeout(timer);
+                    proc.rustTokens.delete(token);
+                    if (proc.rustAwaiting === state) {
+                        proc.rustAwaiting = null;
+                    }
+                });
+        }
+
+        if (context.rustAsync.timedOut) {
+            context.rustAsync = null;
+            throw new Error(`timed out after ${timeoutMs} ms`);
+        }
+
+        // Blocks waiting for the next step continue now
+        const yields = proc.rustYields ?? [];
+        proc.rustYields = [];
+        yields.forEach(resolve => resolve());
+
+        for (const call of [...(proc.rustRings ?? [])]) {
+            call.process.runStep();
+            if (call.process.errorFlag || !call.process.isRunning()) {
+                proc.rustRings.splice(proc.rustRings.indexOf(call), 1);
+                if (call.process.errorFlag) {
+                    call.reject(new Error('error while running ring'));
+                } else {
+                    call.resolve(call.process.homeContext.inputs[0]);
+                }
+            }
+        }
+
+        const state = context.rustAsync;
+        if (state.done) {
+            context.rustAsync = null;
+            if ('error' in state) {
+                throw state.error;
+            }
+            return state.value;
+        }
+
+        proc.pushContext('doYield');
+        proc.pushContext();
+    }
+
+    // C-slots pass their enclosed script, which is wrapped as a ring that can see the calling script's variables
+    function $NO_SPACE_EXTENSION_NAME_script(proc, script) {
+        if (!script) {
+            return null;
+        }
+        return new Context(null, script.blockSequence(), proc.context.outerContext, proc.receiver);
+    }
+
+    function $NO_SPACE_EXTENSION_NAME_fromArray(value) {
+        return ArrayBuffer.isView(value) ? new List(Array.from(value)) : value;
+    }
+
+    // Blocks declared with worker = true run on Web Workers, each with its own instance of the module.
+    // Every running call has a worker to itself, so a long call doesn't hold up the others, and idle workers are reused.
+    const $NO_SPACE_EXTENSION_NAME_workers = { available: typeof Worker !== 'undefined', url: null, idle: [] };
+
+    function $NO_SPACE_EXTENSION_NAME_toPlain(value) {
+        return value instanceof List ? value.itemsArray().map($NO_SPACE_EXTENSION_NAME_toPlain) : value;
+    }
+
+    function $NO_SPACE_EXTENSION_NAME_runLocally(fn, args) {
+        return Promise.resolve().then(() => window.$NO_SPACE_EXTENSION_NAME_fns[fn](...args));
+    }
+
+    function $NO_SPACE_EXTENSION_NAME_spawnWorker() {
+        const workers = $NO_SPACE_EXTENSION_NAME_workers;
+        workers.url ??= URL.createObjectURL(new Blob([`
+            const ready = import('${path}/pkg/$PACKAGE_NAME.js').then(async m => { await m.default(); return m; });
+            // Reported apart from errors in calls, so the main thread can run blocks itself instead
+            ready.catch(error => postMessage({ loadError: String(error?.message ?? error) }));
+            self.onmessage = async e => {
+                const { fn, args } = e.data;
+                const m = await ready.catch(() => null);
+                if (!m) {
+                    return;
+                }
+                try {
+                    postMessage({ value: await m[fn](...args) });
+                } catch (error) {
+                    postMessage({ error: String(error?.message ?? error) });
+                }
+            };`], { type: 'text/javascript' }));
+        return new Worker(workers.url, { type: 'module' });
+    }
+
+    function $NO_SPACE_EXTENSION_NAME_worker(fn, args, token) {
+        const workers = $NO_SPACE_EXTENSION_NAME_workers;
+        // Lists are sent as arrays on both paths, so blocks see the same values either way
+        args = args.map($NO_SPACE_EXTENSION_NAME_toPlain);
+        let worker = null;
+        if (workers.available) {
+            try {
+                worker = workers.idle.pop() ?? $NO_SPACE_EXTENSION_NAME_spawnWorker();
+            } catch {
+                workers.available = false;
+            }
+        }
+        if (!worker) {
+            return $NO_SPACE_EXTENSION_NAME_runLocally(fn, args);
+        }
+
+        return new Promise((resolve, reject) => {
+            let settled = false;
+            const settle = f => x => {
+                settled = true;
+                f(x);
+            };
+            resolve = settle(resolve);
+            reject = settle(reject);
+            // A computation on a worker can't be interrupted, so the worker is dropped and a new one made for later calls
+            token.promise.then(reason => {
+                if (!settled) {
+                    worker.terminate();
+                    reject(new Error(`block was cancelled (${reason})`));
+                }
+            });
+
+            const release = () => {
+                worker.onmessage = worker.onerror = null;
+                if (workers.idle.length < (navigator.hardwareConcurrency ?? 4)) {
+                    workers.idle.push(worker);
+                } else {
+                    worker.terminate();
+                }
+            };
+            const fallBack = () => {
+                // The worker could not load the module, so this and later calls run on the main thread
+                workers.available = false;
+                worker.terminate();
+                workers.idle.forEach(x => x.terminate());
+                workers.idle = [];
+                $NO_SPACE_EXTENSION_NAME_runLocally(fn, args).then(resolve, reject);
+            };
+            worker.onmessage = e => {
+                if ('loadError' in e.data) {
+                    fallBack();
+                } else if ('error' in e.data) {
+                    // The module may be left in a bad state, e.g. after a panic, so the worker is not reused
+                    worker.terminate();
+                    reject(new Error(e.data.error));
+                } else {
+                    release();
+                    resolve(e.data.value);
+                }
+            };
+            worker.onerror = e => {
+                e.preventDefault();
+                fallBack();
+            };
+            try {
+                worker.postMessage({ fn, args });
+            } catch {
+                release();
+                reject(new Error('blocks run on a worker only take text, numbers, booleans and lists'));
+            }
+        });
+    }
+
+    // State registered with persist is saved in the stage's XML and handed back when the role is opened
+    let $NO_SPACE_EXTENSION_NAME_loadedState = null;
+    let $NO_SPACE_EXTENSION_NAME_stateLoadPending = false;
+
+    function $NO_SPACE_EXTENSION_NAME_stateError(action, error) {
+        console.error(error);
+        world.children[0]?.showMessage?.(`$EXTENSION_NAME could not ${action} its state:\n${error?.message ?? error}`);
+    }
+
+    // Roles can be opened before the module is initialized, in which case the state is loaded once it is
+    function $NO_SPACE_EXTENSION_NAME_loadState() {
+        const fns = window.$NO_SPACE_EXTENSION_NAME_fns;
+        if (!fns?.netsblox_extension_load_state) {
+            $NO_SPACE_EXTENSION_NAME_stateLoadPending = true;
+            return;
+        }
+        const state = $NO_SPACE_EXTENSION_NAME_loadedState;
+        $NO_SPACE_EXTENSION_NAME_loadedState = null;
+        $NO_SPACE_EXTENSION_NAME_stateLoadPending = false;
+        try {
+            fns.netsblox_extension_load_state(state);
+        } catch (error) {
+            $NO_SPACE_EXTENSION_NAME_stateError('load', error);
+        }
+    }
+    window.$NO_SPACE_EXTENSION_NAME_initialized = () => {
+        if ($NO_SPACE_EXTENSION_NAME_stateLoadPending) {
+            $NO_SPACE_EXTENSION_NAME_loadState();
+        }
+    };
+
+    const $NO_SPACE_EXTENSION_NAME_toXML = StageMorph.prototype.toXML;
+    StageMorph.prototype.toXML = function (serializer) {
+        const xml = $NO_SPACE_EXTENSION_NAME_toXML.apply(this, arguments);
+        let state = null;
+        try {
+            state = window.$NO_SPACE_EXTENSION_NAME_fns?.netsblox_extension_save_state();
+        } catch (error) {
+            $NO_SPACE_EXTENSION_NAME_stateError('save', error); // The project is still saved, just without the extension's state
+        }
+        if (state === null || state === undefined) {
+            return xml;
+        }
+        return $NO_SPACE_EXTENSION_NAME_insertState(xml, serializer.format('<rust-state extension="@">$</rust-state>', '$NO_SPACE_EXTENSION_NAME', state));
+    };
+
+    const $NO_SPACE_EXTENSION_NAME_loadScene = SnapSerializer.prototype.loadScene;
+    SnapSerializer.prototype.loadScene = function (xmlNode) {
+        const stage = xmlNode.childNamed('stage');
+        const state = stage?.children.find(x => x.tag === 'rust-state' && x.attributes.extension === '$NO_SPACE_EXTENSION_NAME');
+        $NO_SPACE_EXTENSION_NAME_loadedState = state ? state.contents : null;
+        return $NO_SPACE_EXTENSION_NAME_loadScene.apply(this, arguments);
+    };
+
     class $NO_SPACE_EXTENSION_NAME extends Extension {
         constructor(ide) {
             super('$EXTENSION_NAME');
+$INJECT_CONSTRUCTOR
         }
 
         onOpenRole() {
-
+            window.$NO_SPACE_EXTENSION_NAME_fns?.netsblox_extension_open_role();
+            $NO_SPACE_EXTENSION_NAME_loadState();
+$INJECT_ON_OPEN_ROLE
         }
 
         getSettings() {
@@ -48,8 +347,22 @@ $LABELPARTS
             ];
         }
 
+$INJECT_METHODS
     }
 
+    // Show help text for this extension's blocks, deferring to NetsBlox for all others
+    const $NO_SPACE_EXTENSION_NAME_help = {
+$BLOCK_HELP
+    };
+    const $NO_SPACE_EXTENSION_NAME_showHelp = BlockMorph.prototype.showHelp;
+    BlockMorph.prototype.showHelp = function () {
+        const help = $NO_SPACE_EXTENSION_NAME_help[this.selector];
+        if (help === undefined) {
+            return $NO_SPACE_EXTENSION_NAME_showHelp.apply(this, arguments);
+        }
+        new DialogBoxMorph().inform('Help', help, this.world(), this.fullImage());
+    };
+
     NetsBloxExtensions.register($NO_SPACE_EXTENSION_NAME);
     let path = document.currentScript.src;
     path = path.substring(0, path.lastIndexOf("/"));
@@ -62,6 +375,8 @@ $LABELPARTS
 
         window.$NO_SPACE_EXTENSION_NAME_fns = {};
 $WINDOW_IMPORTS
+        window.$NO_SPACE_EXTENSION_NAME_initialized();
+$INJECT_AFTER_INIT
         `;
     document.body.appendChild(s);
 })();
\ No newline at end of file
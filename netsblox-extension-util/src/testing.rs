@@ -0,0 +1,326 @@
+use std::{cell::{Cell, RefCell}, future::Future, pin::pin, rc::Rc, task::{Context, Poll, Waker}};
+
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::JsValue;
+
+use crate::{state::release_state, CancelReason, CancellationToken, Process, Sprite, Stage, Value};
+
+thread_local! {
+    // Counting down, so mock ids never meet the ones NetsBlox morphs get counting up
+    static NEXT_ID: Cell<u32> = const { Cell::new(u32::MAX) };
+}
+
+fn next_id() -> u32 {
+    NEXT_ID.with(|x| {
+        let id = x.get();
+        x.set(id - 1);
+        id
+    })
+}
+
+/// A speech or thought bubble shown by a mock sprite
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bubble {
+    pub text: String,
+    pub thinking: bool,
+}
+
+/// Everything a mock sprite keeps track of, see [`sprite_state`] and [`update_sprite`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteState {
+    pub name: String,
+    pub position: (f64, f64),
+    /// Direction in degrees from -180 to 180, with 90 pointing right
+    pub heading: f64,
+    /// Size as a percentage of the costume's size
+    pub size: f64,
+    /// Index of the current costume, starting at 1, or 0 when wearing no costume
+    pub costume_index: usize,
+    pub bubble: Option<Bubble>,
+    pub visible: bool,
+    pub is_clone: bool,
+}
+
+pub(crate) struct MockSprite {
+    pub(crate) id: u32,
+    pub(crate) state: RefCell<SpriteState>,
+}
+
+pub(crate) struct MockStageState {
+    pub(crate) id: u32,
+    pub(crate) dimensions: Cell<(f64, f64)>,
+    pub(crate) mouse_position: Cell<(f64, f64)>,
+    pub(crate) sprites: RefCell<Vec<Rc<MockSprite>>>,
+}
+
+pub(crate) struct MockProcess {
+    pub(crate) sprite: Option<Rc<MockSprite>>,
+    pub(crate) stage: Rc<MockStageState>,
+    pub(crate) yields: Cell<usize>,
+}
+
+impl MockProcess {
+    pub(crate) fn receiver_id(&self) -> u32 {
+        self.sprite.as_ref().map_or(self.stage.id, |x| x.id)
+    }
+}
+
+impl Sprite {
+    pub(crate) fn mock(mock: Rc<MockSprite>) -> Self {
+        Self { morph: JsValue::UNDEFINED, mock: Some(mock) }
+    }
+}
+
+impl Stage {
+    pub(crate) fn mock(mock: Rc<MockStageState>) -> Self {
+        Self { morph: JsValue::UNDEFINED, mock: Some(mock) }
+    }
+}
+
+impl Process {
+    pub(crate) fn mock(mock: Rc<MockProcess>) -> Self {
+        Self { process: JsValue::UNDEFINED, mock: Some(mock) }
+    }
+}
+
+impl CancellationToken {
+    pub(crate) fn mock(mock: Rc<Cell<Option<CancelReason>>>) -> Self {
+        Self { token: JsValue::UNDEFINED, mock: Some(mock) }
+    }
+}
+
+fn mock_sprite(sprite: &Sprite) -> &Rc<MockSprite> {
+    sprite.mock.as_ref().expect("expected a sprite from a MockStage")
+}
+
+/// A fake stage for calling blocks natively, e.g. in `cargo test`.
+///
+/// It hands out [`Sprite`]s, [`Stage`]s and [`Process`]es backed by plain Rust values instead of NetsBlox, which support
+/// everything but pen trails and switching costumes by value.
+#[derive(Clone)]
+pub struct MockStage {
+    stage: Rc<MockStageState>,
+}
+
+impl Default for MockStage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockStage {
+    /// A 480 by 360 stage without sprites
+    pub fn new() -> Self {
+        Self::with_dimensions(480.0, 360.0)
+    }
+
+    pub fn with_dimensions(width: f64, height: f64) -> Self {
+        Self {
+            stage: Rc::new(MockStageState {
+                id: next_id(),
+                dimensions: Cell::new((width, height)),
+                mouse_position: Cell::new((0.0, 0.0)),
+                sprites: RefCell::new(vec![]),
+            }),
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        Stage::mock(self.stage.clone())
+    }
+
+    pub fn set_mouse_position(&self, x: f64, y: f64) {
+        self.stage.mouse_position.set((x, y));
+    }
+
+    /// Add a visible sprite at the center, facing right and wearing no costume
+    pub fn add_sprite(&self, name: &str) -> Sprite {
+        self.add(SpriteState {
+            name: name.to_string(),
+            position: (0.0, 0.0),
+            heading: 90.0,
+            size: 100.0,
+            costume_index: 0,
+            bubble: None,
+            visible: true,
+            is_clone: false,
+        })
+    }
+
+    /// Add a clone of a sprite, which starts where the sprite is but has its own state
+    pub fn add_clone(&self, sprite: &Sprite) -> Sprite {
+        let state = mock_sprite(sprite).state.borrow().clone();
+        self.add(SpriteState { bubble: None, is_clone: true, ..state })
+    }
+
+    fn add(&self, state: SpriteState) -> Sprite {
+        let mock = Rc::new(MockSprite { id: next_id(), state: RefCell::new(state) });
+        self.stage.sprites.borrow_mut().push(mock.clone());
+        Sprite::mock(mock)
+    }
+
+    /// Remove a sprite or clone, dropping its state like NetsBlox does
+    pub fn remove_sprite(&self, sprite: &Sprite) {
+        let id = mock_sprite(sprite).id;
+        self.stage.sprites.borrow_mut().retain(|x| x.id != id);
+        release_state(id);
+    }
+
+    /// A process run by the sprite, to pass to blocks declared with `pass_proc = true`
+    pub fn process(&self, sprite: &Sprite) -> Process {
+        Process::mock(Rc::new(MockProcess { sprite: Some(mock_sprite(sprite).clone()), stage: self.stage.clone(), yields: Cell::new(0) }))
+    }
+
+    /// A process run by the stage
+    pub fn stage_process(&self) -> Process {
+        Process::mock(Rc::new(MockProcess { sprite: None, stage: self.stage.clone(), yields: Cell::new(0) }))
+    }
+}
+
+/// The state of a mock sprite, for asserting what a block did
+pub fn sprite_state(sprite: &Sprite) -> SpriteState {
+    mock_sprite(sprite).state.borrow().clone()
+}
+
+/// Change the state of a mock sprite, e.g. to set it up before calling a block
+pub fn update_sprite(sprite: &Sprite, f: impl FnOnce(&mut SpriteState)) {
+    f(&mut mock_sprite(sprite).state.borrow_mut());
+}
+
+/// How many times blocks yielded on a mock process with [`Process::yield_now`]
+pub fn yield_count(process: &Process) -> usize {
+    process.mock.as_ref().expect("expected a process from a MockStage").yields.get()
+}
+
+/// A token for async blocks that is only cancelled by [`cancel`]
+pub fn cancellation_token() -> CancellationToken {
+    CancellationToken::mock(Rc::new(Cell::new(None)))
+}
+
+pub fn cancel(token: &CancellationToken, reason: CancelReason) {
+    token.mock.as_ref().expect("expected a token from cancellation_token").set(Some(reason));
+}
+
+/// Run an async block natively.
+///
+/// Mock processes never wait for NetsBlox, so this panics if the block waits for anything else, e.g. a real RPC.
+pub fn block_on<F: Future>(f: F) -> F::Output {
+    match pin!(f).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(x) => x,
+        Poll::Pending => panic!("block is waiting for something that is not available natively"),
+    }
+}
+
+/// Convert a NetsBlox-like value to a block parameter, e.g. `input::<Data<Vec<f64>>>(list![1, "2.5"])`.
+///
+/// Numbers given as text and lists of `[key, value]` pairs are accepted where NetsBlox would accept them.
+pub fn input<T: DeserializeOwned>(value: impl Into<Value>) -> T {
+    crate::from_value(value.into()).unwrap_or_else(|e| panic!("invalid block input: {e}"))
+}
+
+/// Convert a block's return value to the value NetsBlox would show
+pub fn output<T: Serialize + ?Sized>(value: &T) -> Value {
+    crate::to_value(value).unwrap_or_else(|e| panic!("invalid block output: {e}"))
+}
+
+/// Build a NetsBlox list from values, e.g. `list![1, "two", list![3.5, true]]`
+#[macro_export]
+macro_rules! list {
+    ($($x:expr),*$(,)?) => {
+        $crate::Value::List(vec![$($crate::Value::from($x)),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Data, ExtensionSetting};
+
+    use super::*;
+
+    #[test]
+    fn mock_stages_track_sprites() {
+        let stage = MockStage::with_dimensions(200.0, 100.0);
+        stage.set_mouse_position(5.0, -5.0);
+        assert_eq!(stage.stage().dimensions().unwrap(), (200.0, 100.0));
+        assert_eq!(stage.stage().mouse_position().unwrap(), (5.0, -5.0));
+
+        let sprite = stage.add_sprite("Turtle");
+        sprite.set_position(10.0, 20.0).unwrap();
+        sprite.set_heading(270.0).unwrap();
+        sprite.think("hmm").unwrap();
+        let clone = stage.add_clone(&sprite);
+
+        let state = sprite_state(&clone);
+        assert_eq!((state.position, state.heading, state.bubble, state.is_clone), ((10.0, 20.0), -90.0, None, true));
+        assert_eq!(sprite_state(&sprite).bubble, Some(Bubble { text: "hmm".into(), thinking: true }));
+        assert_eq!(stage.stage().sprites().unwrap().len(), 2);
+        assert_eq!(sprite_state(&stage.stage().sprite("Turtle").unwrap().unwrap()), sprite_state(&sprite));
+
+        let process = stage.process(&sprite);
+        assert_eq!(sprite_state(&process.sprite().unwrap().unwrap()).name, "Turtle");
+        assert!(stage.stage_process().sprite().unwrap().is_none());
+
+        update_sprite(&sprite, |x| x.visible = false);
+        assert!(!sprite_state(&sprite).visible);
+    }
+
+    #[test]
+    fn removed_sprites_lose_their_state() {
+        let stage = MockStage::new();
+        let sprite = stage.add_sprite("Turtle");
+        sprite.with_state(|x: &mut u32| *x = 3).unwrap();
+        assert_eq!(sprite.with_state(|x: &mut u32| *x), Ok(3));
+        assert_eq!(stage.stage().with_state(|x: &mut u32| *x), Ok(0));
+
+        stage.remove_sprite(&sprite);
+        assert!(stage.stage().sprites().unwrap().is_empty());
+        assert_eq!(sprite.with_state(|x: &mut u32| *x), Ok(0));
+    }
+
+    #[test]
+    fn settings_are_kept_in_memory() {
+        let setting = ExtensionSetting { name: "Loud", id: "testingloud", default_value: true, on_hint: "", off_hint: "", hidden: false };
+        assert!(setting.get());
+        setting.set(false);
+        assert!(!setting.get());
+        setting.set(true);
+        assert!(setting.get());
+    }
+
+    #[test]
+    fn async_blocks_run_natively() {
+        let stage = MockStage::new();
+        let process = stage.stage_process();
+        let token = cancellation_token();
+        let res = block_on(async {
+            process.yield_now().await;
+            process.yield_now().await;
+            token.check()
+        });
+        assert_eq!(res, Ok(()));
+        assert_eq!(yield_count(&process), 2);
+
+        cancel(&token, CancelReason::Timeout);
+        assert_eq!(token.clone().reason(), Some(CancelReason::Timeout));
+    }
+
+    #[test]
+    #[should_panic(expected = "block is waiting for something that is not available natively")]
+    fn waiting_forever_panics() {
+        block_on(std::future::pending::<()>());
+    }
+
+    #[test]
+    fn values_convert_like_netsblox() {
+        let Data(points): Data<Vec<(f64, f64)>> = input(list![list![1, "2.5"], list![-3, 4]]);
+        assert_eq!(points, vec![(1.0, 2.5), (-3.0, 4.0)]);
+        assert_eq!(output(&("a", [true, false], 1.5)), list!["a", list![true, false], 1.5]);
+        assert_eq!(list![], Value::List(vec![]));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid block input: item 1: expected a number, got text \"x\"")]
+    fn invalid_input_panics() {
+        input::<Vec<f64>>(list!["x"]);
+    }
+}
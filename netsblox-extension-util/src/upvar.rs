@@ -0,0 +1,81 @@
+use js_sys::{Array, Function, Reflect};
+use wasm_bindgen::{convert::FromWasmAbi, describe::WasmDescribe, prelude::*};
+
+#[wasm_bindgen(inline_js = r#"
+export function fireHat(selector, values) {
+    const ide = world.children[0];
+    const stage = ide.stage;
+    let started = 0;
+    for (const morph of [stage, ...stage.children]) {
+        if (!(morph instanceof SpriteMorph || morph instanceof StageMorph)) {
+            continue;
+        }
+        for (const block of morph.scripts.children) {
+            if (block.selector !== selector) {
+                continue;
+            }
+            // Upvars of the hat become script variables holding the event payload
+            const frame = new VariableFrame();
+            const upvars = block.inputs().filter(x => x instanceof TemplateSlotMorph);
+            upvars.forEach((upvar, i) => frame.addVar(upvar.evaluate(), values[i] ?? 0));
+            stage.threads.startProcess(block, morph, stage.isThreadSafe, null, null, null, null, null, frame);
+            started += 1;
+        }
+    }
+    return started;
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = fireHat)]
+    fn fire_hat_js(selector: &str, values: Array) -> u32;
+}
+
+/// A variable created by an `%upvar` slot, which the block can set for the script it encloses.
+///
+/// The default variable name is given with the block's `defaults`, e.g. `spec = "for each %upvar in %l %cs", defaults = "['item']"`.
+#[derive(Debug, Clone)]
+pub struct Upvar {
+    name: String,
+    variables: JsValue,
+}
+
+impl Upvar {
+    /// Recover an upvar from the value the generated extension passes for upvar slots
+    pub fn from_js(value: &JsValue) -> Result<Self, JsValue> {
+        let name = Reflect::get(value, &"upvar".into())?.as_string().ok_or("expected an upvar")?;
+        let variables = Reflect::get(value, &"variables".into())?;
+        Ok(Self { name, variables })
+    }
+
+    /// The variable name chosen by the student
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the variable, creating it in the calling script if needed
+    pub fn set(&self, value: &JsValue) -> Result<(), JsValue> {
+        let add_var = Reflect::get(&self.variables, &"addVar".into())?.dyn_into::<Function>()?;
+        add_var.call2(&self.variables, &self.name.as_str().into(), value)?;
+        Ok(())
+    }
+}
+
+impl WasmDescribe for Upvar {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl FromWasmAbi for Upvar {
+    type Abi = <JsValue as FromWasmAbi>::Abi;
+
+    unsafe fn from_abi(js: Self::Abi) -> Self {
+        Upvar::from_js(&JsValue::from_abi(js)).unwrap_or_else(|e| wasm_bindgen::throw_val(e))
+    }
+}
+
+/// Start every script under a hat block from this extension, setting the hat's upvars to the given values in order.
+/// Returns the number of scripts started.
+pub fn fire_hat(block_name: &str, values: &[JsValue]) -> u32 {
+    fire_hat_js(block_name, values.iter().collect())
+}
@@ -0,0 +1,877 @@
+use std::fmt;
+
+use js_sys::Array;
+use serde::{de::{self, DeserializeOwned, IntoDeserializer, Visitor}, ser::{self, Serialize}, Deserialize};
+use wasm_bindgen::{convert::{FromWasmAbi, IntoWasmAbi}, describe::WasmDescribe, prelude::*};
+
+#[wasm_bindgen(inline_js = r#"
+export function listItems(value) {
+    if (typeof List !== 'undefined' && value instanceof List) {
+        return value.itemsArray();
+    }
+    return Array.isArray(value) ? value : undefined;
+}
+
+export function makeList(items) {
+    // Workers have no List, so blocks run there report arrays
+    return typeof List === 'undefined' ? items : new List(items);
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = listItems)]
+    fn list_items(value: &JsValue) -> Option<Array>;
+    #[wasm_bindgen(js_name = makeList)]
+    fn make_list(items: Array) -> JsValue;
+}
+
+/// A NetsBlox value. Maps and structs are represented as lists of `[key, value]` pairs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn kind(&self) -> String {
+        match self {
+            Value::Number(x) => format!("number {x}"),
+            Value::Text(x) => format!("text {x:?}"),
+            Value::Bool(x) => format!("boolean {x}"),
+            Value::List(x) => format!("list of {} items", x.len()),
+        }
+    }
+
+    /// Convert a value passed in by NetsBlox, with empty slots becoming empty text
+    pub fn from_js(value: &JsValue) -> Result<Self, ValueError> {
+        if let Some(x) = value.as_f64() {
+            return Ok(Value::Number(x));
+        }
+        if let Some(x) = value.as_string() {
+            return Ok(Value::Text(x));
+        }
+        if let Some(x) = value.as_bool() {
+            return Ok(Value::Bool(x));
+        }
+        if value.is_null() || value.is_undefined() {
+            return Ok(Value::Text(String::new()));
+        }
+        match list_items(value) {
+            Some(items) => items.iter().enumerate().map(|(i, x)| Value::from_js(&x).map_err(|e| e.at(PathStep::Index(i)))).collect::<Result<_, _>>().map(Value::List),
+            None => Err(ValueError::new(format!("unsupported value {}", value.js_typeof().as_string().unwrap_or_default()))),
+        }
+    }
+
+    /// Create the JS value NetsBlox stores for this value
+    pub fn to_js(&self) -> JsValue {
+        match self {
+            Value::Number(x) => (*x).into(),
+            Value::Text(x) => x.into(),
+            Value::Bool(x) => (*x).into(),
+            Value::List(x) => make_list(x.iter().map(Value::to_js).collect()),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Number(x)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(x: i32) -> Self {
+        Value::Number(x as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(x: bool) -> Self {
+        Value::Bool(x)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(x: &str) -> Self {
+        Value::Text(x.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(x: String) -> Self {
+        Value::Text(x)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(x: Vec<T>) -> Self {
+        Value::List(x.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Where in a nested value an error happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathStep {
+    /// Index into a list, starting at 0
+    Index(usize),
+    /// Key of a map or field of a struct
+    Key(String),
+}
+
+/// Error converting between NetsBlox values and Rust types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueError {
+    /// Path from the outermost value to where the error happened
+    pub path: Vec<PathStep>,
+    pub message: String,
+}
+
+impl ValueError {
+    fn new(message: impl Into<String>) -> Self {
+        Self { path: vec![], message: message.into() }
+    }
+
+    fn expected(expected: &str, found: &Value) -> Self {
+        Self::new(format!("expected {expected}, got {}", found.kind()))
+    }
+
+    fn at(mut self, step: PathStep) -> Self {
+        self.path.insert(0, step);
+        self
+    }
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Items are numbered from 1 like in NetsBlox
+        for (i, step) in self.path.iter().enumerate() {
+            if i > 0 {
+                write!(f, " > ")?;
+            }
+            match step {
+                PathStep::Index(x) => write!(f, "item {}", x + 1)?,
+                PathStep::Key(x) => write!(f, "{x}")?,
+            }
+        }
+        if !self.path.is_empty() {
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+impl ser::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::new(msg.to_string())
+    }
+}
+
+impl de::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::new(msg.to_string())
+    }
+}
+
+impl From<ValueError> for JsValue {
+    fn from(e: ValueError) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+/// Convert a Rust value to a NetsBlox value
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, ValueError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Convert a NetsBlox value to a Rust value
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, ValueError> {
+    T::deserialize(value)
+}
+
+/// Convert a Rust value to the JS value NetsBlox stores for it
+pub fn to_js<T: Serialize + ?Sized>(value: &T) -> Result<JsValue, ValueError> {
+    Ok(to_value(value)?.to_js())
+}
+
+/// Convert a value passed in by NetsBlox to a Rust value
+pub fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, ValueError> {
+    from_value(Value::from_js(value)?)
+}
+
+/// A block parameter or return value converted with serde, e.g. `Data<Vec<(String, f64)>>` or `Data<MyStruct>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Data<T>(pub T);
+
+impl<T> WasmDescribe for Data<T> {
+    fn describe() {
+        JsValue::describe()
+    }
+}
+
+impl<T: Serialize> IntoWasmAbi for Data<T> {
+    type Abi = <JsValue as IntoWasmAbi>::Abi;
+
+    fn into_abi(self) -> Self::Abi {
+        to_js(&self.0).unwrap_or_else(|e| wasm_bindgen::throw_str(&e.to_string())).into_abi()
+    }
+}
+
+impl<T: DeserializeOwned> FromWasmAbi for Data<T> {
+    type Abi = <JsValue as FromWasmAbi>::Abi;
+
+    unsafe fn from_abi(js: Self::Abi) -> Self {
+        Data(from_js(&JsValue::from_abi(js)).unwrap_or_else(|e| wasm_bindgen::throw_str(&e.to_string())))
+    }
+}
+
+impl<T: Serialize> Serialize for Data<T> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Data<T> {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Data)
+    }
+}
+
+// Maps, structs and enum variants with data become lists of [key, value] pairs
+fn pair(key: Value, value: Value) -> Value {
+    Value::List(vec![key, value])
+}
+
+struct ValueSerializer;
+
+struct SeqSerializer {
+    items: Vec<Value>,
+    variant: Option<&'static str>,
+}
+
+struct MapSerializer {
+    pairs: Vec<Value>,
+    key: Option<Value>,
+    variant: Option<&'static str>,
+}
+
+fn wrap_variant(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(name) => Value::List(vec![pair(name.into(), value)]),
+        None => value,
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ValueError> { Ok(Value::Bool(v)) }
+    fn serialize_i8(self, v: i8) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_i16(self, v: i16) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_i32(self, v: i32) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_i64(self, v: i64) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_u8(self, v: u8) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_u16(self, v: u16) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_u32(self, v: u32) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_u64(self, v: u64) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_f32(self, v: f32) -> Result<Value, ValueError> { Ok(Value::Number(v as f64)) }
+    fn serialize_f64(self, v: f64) -> Result<Value, ValueError> { Ok(Value::Number(v)) }
+    fn serialize_char(self, v: char) -> Result<Value, ValueError> { Ok(Value::Text(v.to_string())) }
+    fn serialize_str(self, v: &str) -> Result<Value, ValueError> { Ok(Value::Text(v.to_string())) }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ValueError> { Ok(Value::List(v.iter().map(|&x| Value::Number(x as f64)).collect())) }
+    // NetsBlox has no null, empty slots hold empty text
+    fn serialize_none(self) -> Result<Value, ValueError> { Ok(Value::Text(String::new())) }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, ValueError> { value.serialize(self) }
+    fn serialize_unit(self) -> Result<Value, ValueError> { Ok(Value::Text(String::new())) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ValueError> { self.serialize_unit() }
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Value, ValueError> { Ok(Value::Text(variant.to_string())) }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Value, ValueError> { value.serialize(self) }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<Value, ValueError> {
+        Ok(wrap_variant(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, ValueError> {
+        Ok(SeqSerializer { items: Vec::with_capacity(len.unwrap_or_default()), variant: None })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, ValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, ValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<SeqSerializer, ValueError> {
+        Ok(SeqSerializer { items: Vec::with_capacity(len), variant: Some(variant) })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, ValueError> {
+        Ok(MapSerializer { pairs: Vec::with_capacity(len.unwrap_or_default()), key: None, variant: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, ValueError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<MapSerializer, ValueError> {
+        Ok(MapSerializer { pairs: Vec::with_capacity(len), key: None, variant: Some(variant) })
+    }
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        let i = self.items.len();
+        self.items.push(value.serialize(ValueSerializer).map_err(|e| e.at(PathStep::Index(i)))?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueError> {
+        Ok(wrap_variant(self.variant, Value::List(self.items)))
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> { self.push(value) }
+    fn end(self) -> Result<Value, ValueError> { self.finish() }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> { self.push(value) }
+    fn end(self) -> Result<Value, ValueError> { self.finish() }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> { self.push(value) }
+    fn end(self) -> Result<Value, ValueError> { self.finish() }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> { self.push(value) }
+    fn end(self) -> Result<Value, ValueError> { self.finish() }
+}
+
+impl MapSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, key: Value, value: &T) -> Result<(), ValueError> {
+        let step = match &key {
+            Value::Text(x) => PathStep::Key(x.clone()),
+            _ => PathStep::Index(self.pairs.len()),
+        };
+        let value = value.serialize(ValueSerializer).map_err(|e| e.at(step))?;
+        self.pairs.push(pair(key, value));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueError> {
+        Ok(wrap_variant(self.variant, Value::List(self.pairs)))
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ValueError> {
+        self.key = Some(key.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        let key = self.key.take().ok_or_else(|| ValueError::new("map value without a key"))?;
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> { self.finish() }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ValueError> { self.push(key.into(), value) }
+    fn end(self) -> Result<Value, ValueError> { self.finish() }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ValueError> { self.push(key.into(), value) }
+    fn end(self) -> Result<Value, ValueError> { self.finish() }
+}
+
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Number(x) => serializer.serialize_f64(*x),
+            Value::Text(x) => serializer.serialize_str(x),
+            Value::Bool(x) => serializer.serialize_bool(*x),
+            Value::List(x) => x.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a NetsBlox value")
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Value, E> { Ok(Value::Bool(v)) }
+            fn visit_i64<E>(self, v: i64) -> Result<Value, E> { Ok(Value::Number(v as f64)) }
+            fn visit_u64<E>(self, v: u64) -> Result<Value, E> { Ok(Value::Number(v as f64)) }
+            fn visit_f64<E>(self, v: f64) -> Result<Value, E> { Ok(Value::Number(v)) }
+            fn visit_str<E>(self, v: &str) -> Result<Value, E> { Ok(Value::Text(v.to_string())) }
+            fn visit_string<E>(self, v: String) -> Result<Value, E> { Ok(Value::Text(v)) }
+            fn visit_none<E>(self) -> Result<Value, E> { Ok(Value::Text(String::new())) }
+            fn visit_unit<E>(self) -> Result<Value, E> { Ok(Value::Text(String::new())) }
+
+            fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+                Value::deserialize(deserializer)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+                let mut items = vec![];
+                while let Some(x) = seq.next_element()? {
+                    items.push(x);
+                }
+                Ok(Value::List(items))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+                let mut pairs = vec![];
+                while let Some((k, v)) = map.next_entry()? {
+                    pairs.push(pair(k, v));
+                }
+                Ok(Value::List(pairs))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl Value {
+    fn into_number(self) -> Result<f64, ValueError> {
+        match self {
+            Value::Number(x) => Ok(x),
+            // Numbers typed into text slots arrive as text
+            Value::Text(ref x) => x.trim().parse().map_err(|_| ValueError::expected("a number", &self)),
+            other => Err(ValueError::expected("a number", &other)),
+        }
+    }
+
+    // Checked as an i128, which holds every integer up to 64 bits exactly, unlike f64 which rounds u64::MAX up to 2^64
+    fn into_integer<T: TryFrom<i128> + fmt::Display>(self, min: T, max: T) -> Result<T, ValueError> {
+        let x = self.into_number()?;
+        // Casting saturates, so numbers beyond i128 fail the conversion too
+        match T::try_from(x as i128) {
+            Ok(res) if x.fract() == 0.0 => Ok(res),
+            _ => Err(ValueError::new(format!("expected an integer from {min} to {max}, got {x}"))),
+        }
+    }
+
+    fn into_list(self) -> Result<Vec<Value>, ValueError> {
+        match self {
+            Value::List(x) => Ok(x),
+            other => Err(ValueError::expected("a list", &other)),
+        }
+    }
+
+    fn into_pairs(self) -> Result<Vec<(Value, Value)>, ValueError> {
+        self.into_list()?.into_iter().enumerate().map(|(i, item)| match item {
+            Value::List(mut x) if x.len() == 2 => {
+                let value = x.pop().unwrap();
+                Ok((x.pop().unwrap(), value))
+            }
+            other => Err(ValueError::expected("a [key, value] pair", &other).at(PathStep::Index(i))),
+        }).collect()
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident => $visit:ident($t:ty)),*$(,)?) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+            visitor.$visit(self.into_integer(<$t>::MIN, <$t>::MAX)?)
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Number(x) if x.fract() == 0.0 && x.abs() < 9007199254740992.0 => visitor.visit_i64(x as i64),
+            Value::Number(x) => visitor.visit_f64(x),
+            Value::Text(x) => visitor.visit_string(x),
+            Value::Bool(x) => visitor.visit_bool(x),
+            Value::List(x) => visitor.visit_seq(SeqAccess { items: x.into_iter().enumerate() }),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Bool(x) => visitor.visit_bool(x),
+            Value::Text(x) if x == "true" => visitor.visit_bool(true),
+            Value::Text(x) if x == "false" => visitor.visit_bool(false),
+            other => Err(ValueError::expected("a boolean", &other)),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_f32(self.into_number()? as f32)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_f64(self.into_number()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Text(ref x) if x.chars().count() == 1 => visitor.visit_char(x.chars().next().unwrap()),
+            other => Err(ValueError::expected("a single character", &other)),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Text(x) => visitor.visit_string(x),
+            Value::Number(x) => visitor.visit_string(x.to_string()),
+            Value::Bool(x) => visitor.visit_string(x.to_string()),
+            other => Err(ValueError::expected("text", &other)),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        let bytes = self.into_list()?.into_iter().enumerate()
+            .map(|(i, x)| x.into_integer(0u8, 255).map_err(|e| e.at(PathStep::Index(i))))
+            .collect::<Result<Vec<_>, _>>()?;
+        visitor.visit_byte_buf(bytes)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Text(x) if x.is_empty() => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Text(x) if x.is_empty() => visitor.visit_unit(),
+            other => Err(ValueError::expected("an empty value", &other)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_seq(SeqAccess { items: self.into_list()?.into_iter().enumerate() })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_map(MapAccess { pairs: self.into_pairs()?.into_iter().enumerate(), value: None })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            // Unit variants are given by name, others as a single [variant, value] pair
+            Value::Text(x) => visitor.visit_enum(x.into_deserializer()),
+            other => {
+                let mut pairs = other.into_pairs()?;
+                if pairs.len() != 1 {
+                    return Err(ValueError::new(format!("expected a single [variant, value] pair, got {} pairs", pairs.len())));
+                }
+                let (variant, value) = pairs.pop().unwrap();
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_unit()
+    }
+}
+
+impl IntoDeserializer<'_, ValueError> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+struct SeqAccess {
+    items: std::iter::Enumerate<std::vec::IntoIter<Value>>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = ValueError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, ValueError> {
+        match self.items.next() {
+            Some((i, x)) => seed.deserialize(x).map(Some).map_err(|e| e.at(PathStep::Index(i))),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess {
+    pairs: std::iter::Enumerate<std::vec::IntoIter<(Value, Value)>>,
+    value: Option<(PathStep, Value)>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = ValueError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, ValueError> {
+        match self.pairs.next() {
+            Some((i, (key, value))) => {
+                let step = match &key {
+                    Value::Text(x) => PathStep::Key(x.clone()),
+                    _ => PathStep::Index(i),
+                };
+                let key = seed.deserialize(key).map_err(|e| e.at(PathStep::Index(i)))?;
+                self.value = Some((step, value));
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, ValueError> {
+        let (step, value) = self.value.take().ok_or_else(|| ValueError::new("map value without a key"))?;
+        seed.deserialize(value).map_err(|e| e.at(step))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.pairs.len())
+    }
+}
+
+struct EnumAccess {
+    variant: Value,
+    value: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = ValueError;
+    type Variant = VariantAccess;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, VariantAccess), ValueError> {
+        let step = match &self.variant {
+            Value::Text(x) => PathStep::Key(x.clone()),
+            _ => PathStep::Index(0),
+        };
+        let variant = seed.deserialize(self.variant)?;
+        Ok((variant, VariantAccess { step, value: self.value }))
+    }
+}
+
+struct VariantAccess {
+    step: PathStep,
+    value: Value,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+    type Error = ValueError;
+
+    fn unit_variant(self) -> Result<(), ValueError> {
+        de::Deserialize::deserialize(self.value).map_err(|e: ValueError| e.at(self.step))
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, ValueError> {
+        seed.deserialize(self.value).map_err(|e| e.at(self.step))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, ValueError> {
+        de::Deserializer::deserialize_seq(self.value, visitor).map_err(|e| e.at(self.step))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, ValueError> {
+        de::Deserializer::deserialize_map(self.value, visitor).map_err(|e| e.at(self.step))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: f64,
+        label: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rect { w: u32, h: u32 },
+    }
+
+    // Goes through serialize_bytes and deserialize_byte_buf like serde_bytes does
+    #[derive(Debug, PartialEq)]
+    struct Bytes(Vec<u8>);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Bytes {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct BytesVisitor;
+            impl Visitor<'_> for BytesVisitor {
+                type Value = Bytes;
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "bytes")
+                }
+                fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                    Ok(Bytes(v))
+                }
+            }
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> (Value, T) {
+        let converted = to_value(value).unwrap();
+        (converted.clone(), from_value(converted).unwrap())
+    }
+
+    #[test]
+    fn structs_round_trip_as_pairs() {
+        let point = Point { x: 1.5, label: Some("a".into()) };
+        let (value, res) = round_trip(&point);
+        assert_eq!(value, vec![vec![Value::from("x"), 1.5.into()], vec!["label".into(), "a".into()]].into());
+        assert_eq!(res, point);
+
+        let point = Point { x: 0.0, label: None };
+        let (value, res) = round_trip(&point);
+        assert_eq!(value, vec![vec![Value::from("x"), 0.0.into()], vec!["label".into(), "".into()]].into());
+        assert_eq!(res, point);
+    }
+
+    #[test]
+    fn enums_round_trip() {
+        let (value, res) = round_trip(&Shape::Empty);
+        assert_eq!((value, res), (Value::from("Empty"), Shape::Empty));
+
+        let (value, res) = round_trip(&Shape::Circle(2.0));
+        assert_eq!(value, vec![vec![Value::from("Circle"), 2.0.into()]].into());
+        assert_eq!(res, Shape::Circle(2.0));
+
+        assert_eq!(round_trip(&Shape::Rect { w: 3, h: 4 }).1, Shape::Rect { w: 3, h: 4 });
+    }
+
+    #[test]
+    fn maps_and_bytes_round_trip() {
+        let map = BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+        let (value, res) = round_trip(&map);
+        assert_eq!(value, vec![vec![Value::from("a"), 1.into()], vec!["b".into(), 2.into()]].into());
+        assert_eq!(res, map);
+
+        let (value, res) = round_trip(&Bytes(vec![0, 7, 255]));
+        assert_eq!(value, vec![0, 7, 255].into());
+        assert_eq!(res, Bytes(vec![0, 7, 255]));
+        assert_eq!(from_value::<Bytes>(vec![1, 256].into()).unwrap_err().to_string(), "item 2: expected an integer from 0 to 255, got 256");
+    }
+
+    #[test]
+    fn errors_give_the_path() {
+        let value = Value::from(vec![Value::from(vec![1]), vec![Value::from(2), "abc".into()].into()]);
+        assert_eq!(from_value::<Vec<Vec<f64>>>(value).unwrap_err().to_string(), "item 2 > item 2: expected a number, got text \"abc\"");
+
+        let value = Value::from(vec![vec![Value::from("x"), "no".into()]]);
+        assert_eq!(from_value::<Point>(value).unwrap_err().to_string(), "x: expected a number, got text \"no\"");
+    }
+
+    #[test]
+    fn integers_are_checked_exactly() {
+        assert!(from_value::<u64>(Value::Number(2f64.powi(64))).is_err());
+        assert!(from_value::<i64>(Value::Number(2f64.powi(63))).is_err());
+        assert_eq!(from_value::<i64>(Value::Number(-(2f64.powi(63)))), Ok(i64::MIN));
+        assert_eq!(from_value::<u8>("12".into()), Ok(12));
+        assert!(from_value::<u8>(Value::Number(1.5)).is_err());
+        assert!(from_value::<u32>(Value::Number(-1.0)).is_err());
+    }
+
+    #[test]
+    fn text_is_not_parsed_as_a_list() {
+        assert_eq!(from_value::<Vec<f64>>("[1, 2]".into()).unwrap_err().to_string(), "expected a list, got text \"[1, 2]\"");
+    }
+}
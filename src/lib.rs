@@ -32,6 +32,13 @@ const CAPS_SETTING: ExtensionSetting = ExtensionSetting {
 pub fn main() {
     panic::set_hook(Box::new(console_error_panic_hook::hook));
     console::log_1(&"Hello from Rust!".to_owned().into());
+
+    // Saved with the project, and reset when opening a project without it
+    persist("remembered", || REMEMBERED.with_borrow(|x| x.clone()), |text: Option<String>| REMEMBERED.set(text.unwrap_or_default()));
+}
+
+thread_local! {
+    static REMEMBERED: std::cell::RefCell<String> = const { std::cell::RefCell::new(String::new()) };
 }
 
 #[wasm_bindgen]
@@ -40,6 +47,7 @@ pub fn hello_world() {
     console::log_1(&"Hello World!".to_owned().into());
 }
 
+/// Logs a greeting for the given name to the browser console.
 #[wasm_bindgen]
 #[netsblox_extension_block(name = "logHelloName", category = "Hello World", spec = "Log Hello %s", target = netsblox_extension_util::TargetObject::Both)]
 pub fn hello_name(name: &str) {
@@ -55,6 +63,9 @@ const LABEL_PART_TIMES: LabelPart = LabelPart {
     readonly: false,
 };
 
+/// Repeats the text the given number of times.
+///
+/// Fractional counts are rounded down.
 #[wasm_bindgen]
 #[netsblox_extension_block(name = "repeatString", category = "operators", spec = "Repeat %s for %times times", target = netsblox_extension_util::TargetObject::Both)]
 pub fn repeat_text(text: &str, times: f64) -> String {
@@ -94,6 +105,9 @@ const LABEL_PART_MENU: LabelPart = LabelPart {
     readonly: true,
 };
 
+#[netsblox_extension_js_block(name = "jsUppercase", category = "operators", spec = "uppercase %s", block_type = netsblox_extension_util::BlockType::Reporter)]
+const JS_UPPERCASE: &str = "function (text) { return text.toString().toUpperCase(); }";
+
 #[wasm_bindgen]
 #[netsblox_extension_block(name = "isEven", category = "operators", spec = "is %num even?", target = netsblox_extension_util::TargetObject::Both)]
 pub fn is_even(num: f64) -> bool {
@@ -115,6 +129,16 @@ pub fn print_hello_world() {
     }
 }
 
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "onRustEvent", category = "control", spec = "when Rust event %upvar", defaults = "['data']", type_override = netsblox_extension_util::BlockType::Hat)]
+pub fn on_rust_event() { }
+
+#[wasm_bindgen]
+#[netsblox_extension_menu_item("Fire Rust Event")]
+pub fn fire_rust_event() {
+    fire_hat("onRustEvent", &["Hello from Rust!".into()]);
+}
+
 #[wasm_bindgen]
 #[netsblox_extension_menu_item("Print Extension Name")]
 pub fn print_extension_name() {
@@ -178,7 +202,276 @@ pub fn picky_boi(v: &JsValue) -> JsValue {
 }
 
 #[wasm_bindgen]
-#[netsblox_extension_block(name = "defaultAdder", category = "operators", spec = "add %n + %n", defaults = "['7', '-4']")]
+#[netsblox_extension_block(name = "defaultAdder", category = "operators", spec = "add %n + %n", defaults = "['7', '-4']", help = "Adds two numbers, starting with 7 and -4.")]
 pub fn default_adder(a: f64, b: f64) -> f64 {
     a + b
 }
+
+/// A running total that students can keep in a variable.
+pub struct Tally {
+    total: f64,
+}
+
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "newTally", category = "operators", spec = "new tally")]
+pub fn new_tally() -> Handle<Tally> {
+    Handle::new(Tally { total: 0.0 })
+}
+
+/// Adds to a tally created by the "new tally" block and reports the new total.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "addToTally", category = "operators", spec = "add %n to tally %s")]
+pub fn add_to_tally(amount: f64, tally: Handle<Tally>) -> Result<f64, HandleError> {
+    tally.with_mut(|tally| {
+        tally.total += amount;
+        tally.total
+    })
+}
+
+/// Counts how many of the numbers the predicate reports true for.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "countMatching", category = "operators", spec = "count %mult%n matching %predRing")]
+pub async fn count_matching(vals: Vec<JsValue>, predicate: Ring) -> Result<f64, JsValue> {
+    let mut count = 0.0;
+    for val in vals {
+        if predicate.call(&[val]).await?.as_bool().unwrap_or(false) {
+            count += 1.0;
+        }
+    }
+    Ok(count)
+}
+
+/// Runs the enclosed script the given number of times, logging each iteration.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "repeatLogged", category = "control", spec = "repeat %n times logged %cs")]
+pub async fn repeat_logged(times: f64, body: Ring) -> Result<(), JsValue> {
+    for i in 0..times as usize {
+        console::log_1(&format!("Iteration {}", i + 1).into());
+        body.call(&[]).await?;
+    }
+    Ok(())
+}
+
+/// Runs the enclosed script once for each value, with the upvar set to the value.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "forEachValue", category = "control", spec = "for each %upvar in %mult%s %cs", defaults = "['value']")]
+pub async fn for_each_value(value: Upvar, values: Vec<JsValue>, body: Ring) -> Result<(), JsValue> {
+    for val in values {
+        value.set(&val)?;
+        body.call(&[]).await?;
+    }
+    Ok(())
+}
+
+/// Reports a ring that scales numbers by the given factor.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "scaler", category = "operators", spec = "scale by %n")]
+pub fn scaler(factor: f64) -> RustRing {
+    RustRing::new(move |args| {
+        let val = args.first().and_then(|x| x.as_f64().or_else(|| x.as_string()?.parse().ok())).unwrap_or_default();
+        (val * factor).into()
+    })
+}
+
+/// Sends a chat message with the given text to a role.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "sendChat", category = "network", spec = "send chat %s to %s", defaults = "['hello', 'everyone in room']")]
+pub fn send_chat(text: &str, target: &str) -> Result<(), JsValue> {
+    send_message(target, &Message::new("chat").with_field("text", text))
+}
+
+/// Reports the current temperature at a location from the Weather service.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "rustTemperature", category = "network", spec = "temperature at lat %n long %n")]
+pub async fn rust_temperature(latitude: f64, longitude: f64) -> Result<JsValue, JsValue> {
+    let res = rpc("Weather", "temperature", &serde_json::json!({ "latitude": latitude, "longitude": longitude })).await?;
+    Ok(match res {
+        RpcValue::Text(text) => text.into(),
+        RpcValue::Json(json) => json.to_string().into(),
+        RpcValue::Image { .. } => JsValue::UNDEFINED,
+    })
+}
+
+/// Moves the sprite to a random position on the stage and says the given text.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "jumpAndSay", category = "motion", spec = "jump somewhere and say %s", target = netsblox_extension_util::TargetObject::SpriteMorph, pass_proc = true)]
+pub fn jump_and_say(proc: Process, text: &str) -> Result<(), JsValue> {
+    let Some(sprite) = proc.sprite()? else { return Ok(()) };
+    let (width, height) = proc.stage()?.dimensions()?;
+    sprite.set_position((js_sys::Math::random() - 0.5) * width, (js_sys::Math::random() - 0.5) * height)?;
+    sprite.say(text)
+}
+
+/// Reports a copy of the costume with its colors inverted.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "invertCostume", category = "looks", spec = "invert %img")]
+pub fn invert_costume(mut image: Image) -> Image {
+    for pixel in image.data_mut().chunks_exact_mut(4) {
+        for channel in &mut pixel[..3] {
+            *channel = 255 - *channel;
+        }
+    }
+    image
+}
+
+/// Reports a sine tone with the given frequency and duration.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "synthesizeTone", category = "sound", spec = "tone %n Hz for %n secs", defaults = "[440, 1]")]
+pub fn synthesize_tone(frequency: f64, seconds: f64) -> Result<Audio, JsValue> {
+    let sample_rate = 44100;
+    let samples = (0..(seconds.max(0.0) * sample_rate as f64) as usize).map(|i| (std::f64::consts::TAU * frequency * i as f64 / sample_rate as f64).sin() as f32 * 0.5).collect();
+    Ok(Audio::mono(sample_rate, samples)?.with_name(format!("{frequency} Hz")))
+}
+
+/// Reports the largest absolute sample value of a sound.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "peakAmplitude", category = "sound", spec = "peak amplitude of %snd")]
+pub async fn peak_amplitude(sound: JsValue) -> Result<f64, JsValue> {
+    let audio = Audio::from_js(&sound).await?;
+    Ok(audio.mixed().iter().fold(0.0f32, |acc, x| acc.max(x.abs())) as f64)
+}
+
+/// Draws a regular polygon centered on the stage with the pen trails.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "drawPolygon", category = "pen", spec = "draw polygon with %n sides radius %n", defaults = "[6, 100]", pass_proc = true)]
+pub fn draw_polygon(proc: Process, sides: f64, radius: f64) -> Result<(), JsValue> {
+    let sides = (sides as usize).max(3);
+    let points = (0..sides).map(|i| {
+        let angle = std::f64::consts::TAU * i as f64 / sides as f64;
+        (radius * angle.cos(), radius * angle.sin())
+    }).collect::<Vec<_>>();
+    proc.stage()?.draw(|pen| {
+        pen.polygon(&points, "rgba(100, 149, 237, 0.5)");
+        pen.text((-radius, -radius - 20.0), &format!("{sides} sides"), 16.0, "black")
+    })?
+}
+
+/// Counts the primes below a limit, yielding regularly so the IDE stays responsive and giving up after a minute.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "countPrimes", category = "operators", spec = "number of primes below %n", defaults = "[1000000]", pass_proc = true, timeout_ms = 60000)]
+pub async fn count_primes(proc: Process, token: CancellationToken, limit: f64) -> Result<f64, Cancelled> {
+    let mut yielder = proc.yielder(10.0);
+    let mut count = 0;
+    for n in 2..limit.max(0.0) as u64 {
+        if (2..).take_while(|d| d * d <= n).all(|d| n % d != 0) {
+            count += 1;
+        }
+        yielder.tick().await;
+        token.check()?;
+    }
+    Ok(count as f64)
+}
+
+/// Reports the nth prime number, computed on a Web Worker.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "nthPrime", category = "operators", spec = "prime number %n", defaults = "[10000]", worker = true)]
+pub fn nth_prime(n: f64) -> f64 {
+    (2u64..).filter(|&x| (2..).take_while(|d| d * d <= x).all(|d| x % d != 0)).nth((n.max(1.0) as usize) - 1).unwrap() as f64
+}
+
+/// Counts how many times the sprite running the block has used it. Clones count separately.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "countMyCalls", category = "sensing", spec = "my call count", pass_proc = true)]
+pub fn count_my_calls(proc: Process) -> Result<f64, JsValue> {
+    proc.with_state(|count: &mut f64| {
+        *count += 1.0;
+        *count
+    })
+}
+
+/// Remembers text, which is saved with the project.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "remember", category = "variables", spec = "remember %s")]
+pub fn remember(text: &str) {
+    REMEMBERED.set(text.to_string());
+}
+
+/// Reports the text given to the remember block.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "remembered", category = "variables", spec = "remembered text")]
+pub fn remembered() -> String {
+    REMEMBERED.with_borrow(|x| x.clone())
+}
+
+#[derive(serde::Serialize)]
+pub struct Bounds {
+    left: f64,
+    right: f64,
+    bottom: f64,
+    top: f64,
+}
+
+/// Reports the bounds of a list of [x, y] points as a list of [key, value] pairs.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "pointBounds", category = "lists", spec = "bounds of points %l")]
+pub fn point_bounds(points: Data<Vec<(f64, f64)>>) -> Data<Bounds> {
+    let mut bounds = Bounds { left: f64::INFINITY, right: f64::NEG_INFINITY, bottom: f64::INFINITY, top: f64::NEG_INFINITY };
+    for (x, y) in points.0 {
+        bounds.left = bounds.left.min(x);
+        bounds.right = bounds.right.max(x);
+        bounds.bottom = bounds.bottom.min(y);
+        bounds.top = bounds.top.max(y);
+    }
+    Data(bounds)
+}
+
+/// XORs every byte of a list of bytes or base64 text with a key from 0 to 255.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "xorBytes", category = "operators", spec = "xor bytes %s with %n", defaults = "['aGVsbG8=', 42]")]
+pub fn xor_bytes(data: &[u8], key: f64) -> Result<Vec<u8>, String> {
+    if key.fract() != 0.0 || !(0.0..=255.0).contains(&key) {
+        return Err(format!("expected a key from 0 to 255, got {key}"));
+    }
+    Ok(data.iter().map(|x| x ^ key as u8).collect())
+}
+
+/// Reports the moving average of a list of numbers over windows of the given size.
+#[wasm_bindgen]
+#[netsblox_extension_block(name = "movingAverage", category = "lists", spec = "moving average of %l over %n", defaults = "[null, 3]")]
+pub fn moving_average(values: Vec<f64>, window: f64) -> Vec<f64> {
+    let window = (window as usize).clamp(1, values.len().max(1));
+    values.windows(window).map(|x| x.iter().sum::<f64>() / window as f64).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use netsblox_extension_util::{list, testing::*};
+
+    use super::*;
+
+    #[test]
+    fn counts_calls_per_sprite() {
+        let stage = MockStage::new();
+        let sprite = stage.add_sprite("Turtle");
+        let clone = stage.add_clone(&sprite);
+        count_my_calls(stage.process(&sprite)).unwrap();
+        assert_eq!(count_my_calls(stage.process(&sprite)).unwrap(), 2.0);
+        assert_eq!(count_my_calls(stage.process(&clone)).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn finds_bounds() {
+        let bounds = point_bounds(input(list![list![1, "2"], list![-3.5, 4]]));
+        assert_eq!(output(&bounds), list![list!["left", -3.5], list!["right", 1], list!["bottom", 2], list!["top", 4]]);
+    }
+
+    #[test]
+    fn counts_primes() {
+        let stage = MockStage::new();
+        let sprite = stage.add_sprite("Turtle");
+        assert_eq!(block_on(count_primes(stage.process(&sprite), cancellation_token(), 20.0)), Ok(8.0));
+    }
+
+    #[test]
+    fn averages_windows() {
+        assert_eq!(moving_average(vec![1.0, 2.0, 3.0, 4.0], 2.0), vec![1.5, 2.5, 3.5]);
+    }
+
+    #[test]
+    fn xors_with_byte_keys() {
+        assert_eq!(xor_bytes(&[1, 2], 3.0), Ok(vec![2, 1]));
+        assert_eq!(xor_bytes(&[1], 300.0), Err("expected a key from 0 to 255, got 300".into()));
+        assert_eq!(xor_bytes(&[1], -1.0), Err("expected a key from 0 to 255, got -1".into()));
+        assert_eq!(xor_bytes(&[1], 1.5), Err("expected a key from 0 to 255, got 1.5".into()));
+    }
+}